@@ -0,0 +1,287 @@
+//! Plays games without a window, as fast as the simulation runs, and prints what happened in
+//! them as JSON. Used to balance towers and waves, and to catch changes that make the game
+//! unwinnable in CI.
+//!
+//! ```text
+//! yatd-sim [--seed <seed>] [--games <count>] [--waves <file>] [--script <file>] [--minutes <minutes>]
+//! ```
+//!
+//! Game `n` is played on the map of `seed + n`. Seeds whose map has no path for the enemies are
+//! listed under `failed_games` and left out of the averages. The wave file is a `.waves.ron` file inside
+//! `assets/`, which is looked up next to the binary unless it is run through `cargo run`. The
+//! script is a RON file listing the commands given in every game, each one with the tick it is
+//! carried out at, 60 ticks making a second:
+//!
+//! ```text
+//! (commands: [
+//!     (0, Build(kind: Cannon, cell: (4, 7))),
+//!     (600, Upgrade(cell: (4, 7), path: 0)),
+//!     (900, CallNextWave),
+//! ])
+//! ```
+use anyhow::{bail, Context};
+use bevy::{
+    app::{Events, ManualEventReader},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, env, fs};
+use yatd_lib::{
+    economy::{GameStats, PlayerResources},
+    enemy::EnemyReachedGoal,
+    game_state::GameState,
+    map::{MapError, MapSeed},
+    projectile::DamageDealt,
+    sim::{
+        headless::{headless_app, HeadlessApp},
+        PlayerCommand, PlayerCommands, SimTime, Simulation, SIM_STEP,
+    },
+    tower::Tower,
+    wave::{WaveAssets, WaveState},
+};
+
+/// Steps run between two updates of the rest of the app, which only handles state changes and
+/// asset loading here
+const STEPS_PER_UPDATE: u64 = 60;
+
+/// The gold curve has a point for every second of game time
+const GOLD_SAMPLE_STEPS: u64 = 60;
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{:#}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> anyhow::Result<()> {
+    let options = Options::from_args()?;
+    let script = match &options.script {
+        Some(path) => {
+            let text =
+                fs::read_to_string(path).with_context(|| format!("Could not read {}", path))?;
+            ron::from_str(&text).with_context(|| format!("{} is not a script", path))?
+        }
+        None => Script::default(),
+    };
+
+    let mut app = headless_app();
+    // Startup systems run with the first update, they start loading the assets
+    app.update();
+    let schedule = app
+        .world
+        .get_resource::<AssetServer>()
+        .unwrap()
+        .load(options.waves.as_str());
+    app.world.get_resource_mut::<WaveAssets>().unwrap().schedule = schedule;
+
+    let max_ticks = (options.minutes * 60.0 / SIM_STEP) as u64;
+    let mut games = Vec::new();
+    let mut failed_games = Vec::new();
+    for n in 0..options.games {
+        let seed = options.seed.wrapping_add(n);
+        match play(&mut app, seed, &script, max_ticks)? {
+            Ok(game) => games.push(game),
+            Err(error) => {
+                eprintln!("Skipping seed {}: {}", seed, error);
+                failed_games.push(FailedGame {
+                    seed,
+                    error: error.to_string(),
+                });
+            }
+        }
+    }
+    let summary = Summary::new(games, failed_games);
+    println!("{}", serde_json::to_string_pretty(&summary)?);
+    Ok(())
+}
+
+struct Options {
+    seed: u64,
+    games: u64,
+    waves: String,
+    script: Option<String>,
+    /// Games still going after that much game time are stopped
+    minutes: f32,
+}
+
+impl Options {
+    fn from_args() -> anyhow::Result<Self> {
+        let mut options = Options {
+            seed: 0,
+            games: 1,
+            waves: "waves/default.waves.ron".to_string(),
+            script: None,
+            minutes: 60.0,
+        };
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .with_context(|| format!("{} needs a value", arg))
+            };
+            match arg.as_str() {
+                "--seed" => options.seed = value()?.parse().context("Invalid seed")?,
+                "--games" => options.games = value()?.parse().context("Invalid game count")?,
+                "--waves" => options.waves = value()?,
+                "--script" => options.script = Some(value()?),
+                "--minutes" => options.minutes = value()?.parse().context("Invalid minutes")?,
+                _ => bail!("Unknown argument {}", arg),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Commands given in every game, each one before the step of its tick
+#[derive(Deserialize, Default)]
+struct Script {
+    commands: Vec<(u64, PlayerCommand)>,
+}
+
+/// Plays a whole game on the map of `seed`, until it is won, lost or runs out of time. The game
+/// is not played when the map has no path for the enemies.
+fn play(
+    app: &mut App,
+    seed: u64,
+    script: &Script,
+    max_ticks: u64,
+) -> anyhow::Result<Result<GameSummary, MapError>> {
+    app.world.insert_resource(MapSeed(seed));
+    app.set_state(GameState::Defense).wait_until_ready()?;
+    if let Some(error) = app.world.get_resource::<MapError>() {
+        return Ok(Err(*error));
+    }
+
+    let mut game = GameRecord::default();
+    let mut damage_reader = new_reader::<DamageDealt>(&app.world);
+    let mut leak_reader = new_reader::<EnemyReachedGoal>(&app.world);
+    let mut commands = script.commands.iter().peekable();
+    loop {
+        let tick = app.world.get_resource::<SimTime>().unwrap().tick();
+        {
+            let mut queue = app.world.get_resource_mut::<PlayerCommands>().unwrap();
+            while let Some((_, command)) = commands.next_if(|(t, _)| *t <= tick) {
+                queue.push(command.clone());
+            }
+        }
+        Simulation::step(&mut app.world);
+
+        let world = &app.world;
+        let damage = world.get_resource::<Events<DamageDealt>>().unwrap();
+        for DamageDealt { tower, amount, .. } in damage_reader.iter(damage) {
+            // Towers sold since the projectile was fired are not counted
+            if let Some(tower) = world.get::<Tower>(*tower) {
+                *game.damage.entry(format!("{:?}", tower.kind)).or_default() += amount;
+            }
+        }
+        let leaks = world.get_resource::<Events<EnemyReachedGoal>>().unwrap();
+        game.leaks += leak_reader.iter(leaks).count() as u32;
+        let resources = world.get_resource::<PlayerResources>().unwrap();
+        if (tick + 1) % GOLD_SAMPLE_STEPS == 0 {
+            game.gold_curve.push(resources.gold);
+        }
+
+        let wave_state = world.get_resource::<WaveState>().unwrap();
+        let over = resources.lives == 0 || wave_state.is_finished();
+        if over || tick + 1 >= max_ticks {
+            break;
+        }
+        if (tick + 1) % STEPS_PER_UPDATE == 0 {
+            app.update();
+        }
+    }
+
+    let summary = game.summarize(seed, &app.world);
+    app.set_state(GameState::End);
+    Ok(Ok(summary))
+}
+
+/// Reader that skips the events still left from the previous game
+fn new_reader<T: Send + Sync + 'static>(world: &World) -> ManualEventReader<T> {
+    let mut reader = ManualEventReader::default();
+    reader
+        .iter(world.get_resource::<Events<T>>().unwrap())
+        .for_each(drop);
+    reader
+}
+
+/// What is collected while a game is played
+#[derive(Default)]
+struct GameRecord {
+    leaks: u32,
+    gold_curve: Vec<u32>,
+    /// Damage dealt by every kind of tower
+    damage: BTreeMap<String, f32>,
+}
+
+impl GameRecord {
+    fn summarize(self, seed: u64, world: &World) -> GameSummary {
+        let resources = world.get_resource::<PlayerResources>().unwrap();
+        let stats = world.get_resource::<GameStats>().unwrap();
+        let wave_state = world.get_resource::<WaveState>().unwrap();
+        let seconds = world.get_resource::<SimTime>().unwrap().elapsed_seconds();
+        GameSummary {
+            seed,
+            won: resources.lives > 0 && wave_state.is_finished(),
+            waves_survived: stats.waves_cleared,
+            waves_total: wave_state.total,
+            leaks: self.leaks,
+            lives_left: resources.lives,
+            seconds,
+            gold_curve: self.gold_curve,
+            dps_per_tower_kind: self
+                .damage
+                .into_iter()
+                .map(|(kind, damage)| (kind, damage / seconds.max(SIM_STEP)))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct GameSummary {
+    seed: u64,
+    won: bool,
+    waves_survived: u32,
+    waves_total: usize,
+    /// Enemies that reached the goal
+    leaks: u32,
+    lives_left: u32,
+    /// Game time played
+    seconds: f32,
+    /// Gold in the bank at the end of every second
+    gold_curve: Vec<u32>,
+    /// Damage dealt over the whole game, per second
+    dps_per_tower_kind: BTreeMap<String, f32>,
+}
+
+/// A seed whose game could not be played
+#[derive(Serialize)]
+struct FailedGame {
+    seed: u64,
+    error: String,
+}
+
+#[derive(Serialize)]
+struct Summary {
+    win_rate: f32,
+    average_waves_survived: f32,
+    average_leaks: f32,
+    games: Vec<GameSummary>,
+    failed_games: Vec<FailedGame>,
+}
+
+impl Summary {
+    fn new(games: Vec<GameSummary>, failed_games: Vec<FailedGame>) -> Self {
+        let count = games.len().max(1) as f32;
+        Self {
+            win_rate: games.iter().filter(|g| g.won).count() as f32 / count,
+            average_waves_survived: games.iter().map(|g| g.waves_survived as f32).sum::<f32>()
+                / count,
+            average_leaks: games.iter().map(|g| g.leaks as f32).sum::<f32>() / count,
+            games,
+            failed_games,
+        }
+    }
+}
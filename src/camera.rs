@@ -1,4 +1,7 @@
-use crate::game_state::GameState;
+use crate::{
+    game_state::{GameState, StateScoped},
+    settings::Settings,
+};
 use bevy::prelude::*;
 use bevy_mod_picking::PickingCameraBundle;
 use leafwing_input_manager::{
@@ -29,12 +32,12 @@ impl CameraPlugin {
 
 impl Plugin for CameraPlugin {
     fn build(&self, app: &mut App) {
+        app.init_resource::<Settings>();
         if let Some(desired_state) = self.desired_state {
             let p = InputManagerPlugin::<CameraAction, GameState>::run_in_state(desired_state);
             app.add_plugin(p)
                 .add_system_set(SystemSet::on_enter(desired_state).with_system(setup))
-                .add_system_set(SystemSet::on_update(desired_state).with_system(camera_controller))
-                .add_system_set(SystemSet::on_exit(desired_state).with_system(destroy));
+                .add_system_set(SystemSet::on_update(desired_state).with_system(camera_controller));
         } else {
             //panic!("CameraPlugin::run_in_state() must be called with a GameState");
             app.add_plugin(InputManagerPlugin::<CameraAction>::default())
@@ -69,15 +72,13 @@ fn setup(mut commands: Commands) {
                 ..Default::default()
             },
         })
-        .insert_bundle(PickingCameraBundle::default());
-}
-
-fn destroy(mut commands: Commands, query: Query<Entity, With<Camera>>) {
-    commands.entity(query.single()).despawn_recursive();
+        .insert_bundle(PickingCameraBundle::default())
+        .insert(StateScoped(GameState::Defense));
 }
 
 pub fn camera_controller(
     time: Res<Time>,
+    settings: Res<Settings>,
     mut camera: Query<(&mut LookTransform, &Transform, &Camera)>,
     actions: Query<&ActionState<CameraAction>>,
     //input: Res<InputBindings>,
@@ -91,9 +92,10 @@ pub fn camera_controller(
 
     let actions = actions.single();
     let delta = time.delta_seconds() as f32;
+    let speed = camera.speed * settings.camera_speed;
     for direction in CameraAction::DIRECTIONS {
         if actions.pressed(&direction) {
-            let increment = direction.scene_direction(scene_transform) * camera.speed * delta;
+            let increment = direction.scene_direction(scene_transform) * speed * delta;
             let new_position = camera_transform.eye + increment;
 
             // Check if the new position is in bounds
@@ -0,0 +1,184 @@
+use crate::{
+    enemy::EnemyReachedGoal,
+    game_state::GameState,
+    projectile::EnemyKilled,
+    sim::{CommandSystem, SimSystem, SimulationApp},
+    tower::TowerSold,
+    wave::WaveCleared,
+};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// https://github.com/Leafwing-Studios/leafwing-input-manager/blob/446ac84cfcd2c76ae5607cca1c871681af09a0d9/src/lib.rs#L98
+#[derive(Default)]
+pub struct EconomyPlugin {
+    desired_state: Option<GameState>,
+}
+
+impl EconomyPlugin {
+    pub fn new() -> Self {
+        Self {
+            desired_state: None,
+        }
+    }
+
+    pub fn run_in_state(state: GameState) -> Self {
+        Self {
+            desired_state: Some(state),
+        }
+    }
+}
+
+impl Plugin for EconomyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PlayerResources>()
+            .init_resource::<GameStats>();
+        if let Some(desired_state) = self.desired_state {
+            app.add_system_set(SystemSet::on_enter(desired_state).with_system(setup))
+                .add_system_set(SystemSet::on_update(desired_state).with_system(track_time))
+                // Gold decides what the player's commands can buy, it must be the same at every
+                // step every time the game is played
+                .add_command_system_set(
+                    SystemSet::new().with_system(refund_sold_towers.after(CommandSystem::Apply)),
+                )
+                .add_sim_system_set(
+                    SystemSet::new()
+                        .label(SimSystem::Stats)
+                        .after(SimSystem::Combat)
+                        .with_system(collect_bounties)
+                        .with_system(reward_cleared_waves)
+                        .with_system(lose_lives),
+                );
+        } else {
+            panic!("EconomyPlugin::run_in_state() must be called with a GameState");
+        }
+    }
+}
+
+pub const STARTING_GOLD: u32 = 150;
+pub const STARTING_LIVES: u32 = 20;
+
+/// Gold for clearing the first wave, every later wave is worth [`WAVE_REWARD_STEP`] more
+const WAVE_REWARD_BASE: u32 = 20;
+const WAVE_REWARD_STEP: u32 = 5;
+
+/// Share of the gold left in the bank that is paid out when a wave is cleared
+const INTEREST_RATE: f32 = 0.05;
+const MAX_INTEREST: u32 = 50;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlayerResources {
+    /// Spent on building and upgrading towers
+    pub gold: u32,
+    /// The game is lost once they run out
+    pub lives: u32,
+    pub score: u32,
+}
+
+impl Default for PlayerResources {
+    fn default() -> Self {
+        Self {
+            gold: STARTING_GOLD,
+            lives: STARTING_LIVES,
+            score: 0,
+        }
+    }
+}
+
+impl PlayerResources {
+    pub fn can_afford(&self, cost: u32) -> bool {
+        self.gold >= cost
+    }
+
+    /// Takes `cost` gold, returns false and leaves the gold untouched if there is not enough
+    pub fn spend(&mut self, cost: u32) -> bool {
+        if !self.can_afford(cost) {
+            return false;
+        }
+        self.gold -= cost;
+        true
+    }
+
+    /// Gold paid out when a wave is cleared
+    pub fn interest(&self) -> u32 {
+        ((self.gold as f32 * INTEREST_RATE) as u32).min(MAX_INTEREST)
+    }
+}
+
+/// What happened during a game, shown once it ends
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct GameStats {
+    pub enemies_killed: u32,
+    pub waves_cleared: u32,
+    /// From bounties, wave rewards and interest. Refunds from sold towers are not counted.
+    pub gold_earned: u32,
+    /// Seconds spent in the game
+    pub time_played: f32,
+}
+
+fn setup(mut resources: ResMut<PlayerResources>, mut stats: ResMut<GameStats>) {
+    *resources = PlayerResources::default();
+    *stats = GameStats::default();
+}
+
+fn collect_bounties(
+    mut events: EventReader<EnemyKilled>,
+    mut resources: ResMut<PlayerResources>,
+    mut stats: ResMut<GameStats>,
+) {
+    for EnemyKilled { bounty, .. } in events.iter() {
+        resources.gold += bounty;
+        resources.score += bounty;
+        stats.enemies_killed += 1;
+        stats.gold_earned += bounty;
+    }
+}
+
+fn refund_sold_towers(mut events: EventReader<TowerSold>, mut resources: ResMut<PlayerResources>) {
+    for TowerSold { refund, .. } in events.iter() {
+        resources.gold += refund;
+    }
+}
+
+fn reward_cleared_waves(
+    mut events: EventReader<WaveCleared>,
+    mut resources: ResMut<PlayerResources>,
+    mut stats: ResMut<GameStats>,
+) {
+    for WaveCleared { index } in events.iter() {
+        let reward = WAVE_REWARD_BASE + WAVE_REWARD_STEP * *index as u32;
+        let interest = resources.interest();
+        resources.gold += reward + interest;
+        resources.score += reward;
+        stats.waves_cleared += 1;
+        stats.gold_earned += reward + interest;
+        info!(
+            "Wave {} cleared: {} gold, {} interest",
+            index + 1,
+            reward,
+            interest
+        );
+    }
+}
+
+fn lose_lives(
+    mut events: EventReader<EnemyReachedGoal>,
+    mut resources: ResMut<PlayerResources>,
+    mut game_state: ResMut<State<GameState>>,
+) {
+    if resources.lives == 0 {
+        return;
+    }
+    for EnemyReachedGoal { damage, .. } in events.iter() {
+        resources.lives = resources.lives.saturating_sub(*damage);
+    }
+    if resources.lives == 0 {
+        if let Err(e) = game_state.set(GameState::End) {
+            warn!("Could not end the game: {}", e);
+        }
+    }
+}
+
+fn track_time(time: Res<Time>, mut stats: ResMut<GameStats>) {
+    stats.time_played += time.delta_seconds();
+}
@@ -0,0 +1,177 @@
+use crate::{
+    economy::{GameStats, PlayerResources},
+    game_state::{GameState, StateScoped},
+    map::{self, MapSeed},
+};
+use bevy::prelude::*;
+
+#[derive(Default)]
+pub struct EndScreenPlugin {
+    desired_state: Option<GameState>,
+}
+
+impl EndScreenPlugin {
+    pub fn new() -> Self {
+        Self {
+            desired_state: None,
+        }
+    }
+
+    pub fn run_in_state(state: GameState) -> Self {
+        Self {
+            desired_state: Some(state),
+        }
+    }
+}
+
+impl Plugin for EndScreenPlugin {
+    fn build(&self, app: &mut App) {
+        if let Some(desired_state) = self.desired_state {
+            app.add_system_set(SystemSet::on_enter(desired_state).with_system(setup))
+                .add_system_set(SystemSet::on_update(desired_state).with_system(button_selection));
+        } else {
+            panic!("EndScreenPlugin::run_in_state() must be called with a GameState");
+        }
+    }
+}
+
+const TEXT_COLOR: Color = Color::rgb(0.8, 0.8, 0.8);
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    resources: Res<PlayerResources>,
+    stats: Res<GameStats>,
+) {
+    let font = asset_server.load("fonts/FiraMono-Regular.ttf");
+    let text = |value: String, font_size: f32| TextBundle {
+        style: Style {
+            margin: Rect::all(Val::Px(8.)),
+            ..Default::default()
+        },
+        text: Text::with_section(
+            value,
+            TextStyle {
+                font: font.clone(),
+                font_size,
+                color: TEXT_COLOR,
+            },
+            TextAlignment {
+                vertical: VerticalAlign::Center,
+                horizontal: HorizontalAlign::Center,
+            },
+        ),
+        ..Default::default()
+    };
+
+    let title = if resources.lives == 0 {
+        "Defeat"
+    } else {
+        "Victory!"
+    };
+    let minutes = stats.time_played as u32 / 60;
+    let seconds = stats.time_played as u32 % 60;
+    let lines = [
+        format!("Waves survived: {}", stats.waves_cleared),
+        format!("Enemies killed: {}", stats.enemies_killed),
+        format!("Gold earned: {}", stats.gold_earned),
+        format!("Score: {}", resources.score),
+        format!("Time played: {}:{:02}", minutes, seconds),
+    ];
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect::all(Val::Px(0.)),
+                margin: Rect::all(Val::Px(16.)),
+                padding: Rect::all(Val::Px(16.)),
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            color: Color::rgba(0.1, 0.1, 0.1, 0.8).into(),
+            ..Default::default()
+        })
+        .insert(StateScoped(GameState::End))
+        .insert(Name::new("end_screen"))
+        .with_children(|parent| {
+            parent.spawn_bundle(text(title.to_string(), 64.0));
+            for line in lines {
+                parent.spawn_bundle(text(line, 24.0));
+            }
+
+            for (label, action) in [
+                ("Restart", ButtonAction::Restart),
+                ("Main Menu", ButtonAction::MainMenu),
+            ] {
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            min_size: Size::new(Val::Px(300.), Val::Px(60.)),
+                            margin: Rect::all(Val::Px(8.)),
+                            padding: Rect::all(Val::Px(8.)),
+                            align_items: AlignItems::Center,
+                            justify_content: JustifyContent::Center,
+                            ..Default::default()
+                        },
+                        color: Color::rgb(0.1, 0.1, 0.1).into(),
+                        ..Default::default()
+                    })
+                    .insert(Name::new(format!("button:{}", label)))
+                    .insert(action)
+                    .with_children(|parent| {
+                        parent.spawn_bundle(text(label.to_string(), 36.0));
+                    });
+            }
+        });
+}
+
+#[allow(clippy::type_complexity)]
+fn button_selection(
+    mut commands: Commands,
+    mut game_state: ResMut<State<GameState>>,
+    mut seed: ResMut<MapSeed>,
+    mut interaction_query: Query<
+        (&Interaction, &mut UiColor, &ButtonAction),
+        (Changed<Interaction>, With<Button>),
+    >,
+) {
+    for (interaction, mut color, button_action) in interaction_query.iter_mut() {
+        match *interaction {
+            Interaction::Clicked => {
+                *color = Color::rgb(0.3, 0.3, 0.3).into();
+                button_action.run(&mut commands, &mut game_state, &mut seed);
+            }
+            Interaction::Hovered => {
+                *color = Color::rgb(0.2, 0.2, 0.2).into();
+            }
+            Interaction::None => {
+                *color = Color::rgb(0.1, 0.1, 0.1).into();
+            }
+        }
+    }
+}
+
+#[derive(Component, Clone, Copy)]
+enum ButtonAction {
+    Restart,
+    MainMenu,
+}
+
+impl ButtonAction {
+    fn run(&self, commands: &mut Commands, game_state: &mut State<GameState>, seed: &mut MapSeed) {
+        let result = match self {
+            ButtonAction::Restart => {
+                // A new map, every Defense plugin sets itself up again when the state is entered
+                map::roll_seed(commands, seed);
+                game_state.set(GameState::Defense)
+            }
+            ButtonAction::MainMenu => game_state.set(GameState::StartMenu),
+        };
+        if let Err(e) = result {
+            warn!("Could not leave the end screen: {}", e);
+        }
+    }
+}
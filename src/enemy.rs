@@ -0,0 +1,266 @@
+use crate::{
+    game_state::{GameState, StateScoped},
+    map::{find_path, EnemyPath, MapConfig, PathRules, Surface},
+    sim::{SimSystem, SimTime, SimulationApp},
+};
+use bevy::prelude::*;
+use serde::Deserialize;
+
+// https://github.com/Leafwing-Studios/leafwing-input-manager/blob/446ac84cfcd2c76ae5607cca1c871681af09a0d9/src/lib.rs#L98
+#[derive(Default)]
+pub struct EnemyPlugin {
+    desired_state: Option<GameState>,
+}
+
+impl EnemyPlugin {
+    pub fn new() -> Self {
+        Self {
+            desired_state: None,
+        }
+    }
+
+    pub fn run_in_state(state: GameState) -> Self {
+        Self {
+            desired_state: Some(state),
+        }
+    }
+}
+
+impl Plugin for EnemyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SpawnEnemy>()
+            .add_event::<EnemyReachedGoal>();
+        if self.desired_state.is_some() {
+            app.add_sim_system_set(
+                SystemSet::new()
+                    .label(SimSystem::Waves)
+                    .after(SimSystem::Propagate)
+                    .with_system(spawn_enemies.label(EnemySystem::Spawn)),
+            )
+            .add_sim_system_set(
+                SystemSet::new()
+                    .label(SimSystem::Movement)
+                    .after(SimSystem::Waves)
+                    .with_system(follow_new_path.before(EnemySystem::Move))
+                    .with_system(move_enemies.label(EnemySystem::Move)),
+            );
+        } else {
+            panic!("EnemyPlugin::run_in_state() must be called with a GameState");
+        }
+    }
+}
+
+#[derive(SystemLabel, Clone, Hash, Debug, PartialEq, Eq)]
+pub enum EnemySystem {
+    Spawn,
+    Move,
+}
+
+/// Spawns an enemy at the start of the [`EnemyPath`]
+pub struct SpawnEnemy {
+    pub kind: EnemyKind,
+    /// Wave the enemy belongs to, if any
+    pub wave: Option<usize>,
+}
+
+/// Sent when an enemy reaches the end of the [`EnemyPath`], right before it is despawned
+pub struct EnemyReachedGoal {
+    pub enemy: Entity,
+    pub damage: u32,
+}
+
+fn spawn_enemies(
+    mut commands: Commands,
+    mut events: EventReader<SpawnEnemy>,
+    config: Res<MapConfig>,
+    surface: Res<Surface>,
+    path: Option<Res<EnemyPath>>,
+) {
+    let path = match path {
+        Some(path) => path,
+        None => return,
+    };
+    for SpawnEnemy { kind, wave } in events.iter() {
+        spawn_enemy(
+            &mut commands,
+            *kind,
+            *wave,
+            path.waypoints(&surface, config.block_size),
+        );
+    }
+}
+
+/// Spawns an enemy at the first of `waypoints`. Its mesh is added by the
+/// [`RenderingPlugin`](crate::rendering::RenderingPlugin).
+pub fn spawn_enemy(
+    commands: &mut Commands,
+    kind: EnemyKind,
+    wave: Option<usize>,
+    waypoints: Vec<Vec3>,
+) {
+    let start = match waypoints.first() {
+        Some(start) => *start,
+        None => return,
+    };
+    let enemy = Enemy {
+        wave,
+        ..Enemy::new(kind)
+    };
+    commands
+        .spawn_bundle((
+            Transform::from_translation(start + Vec3::Y * enemy.radius)
+                .with_scale(Vec3::splat(enemy.radius)),
+            GlobalTransform::default(),
+        ))
+        .insert(enemy)
+        .insert(StateScoped(GameState::Defense))
+        .insert(PathFollower {
+            waypoints,
+            next: 1,
+            travelled: 0.0,
+        })
+        .insert(Name::new(format!("enemy:{:?}", kind)));
+}
+
+/// Gives every enemy a new route from where it stands whenever the [`EnemyPath`] changes
+fn follow_new_path(
+    config: Res<MapConfig>,
+    surface: Res<Surface>,
+    rules: Res<PathRules>,
+    path: Option<Res<EnemyPath>>,
+    mut query: Query<(&Transform, &mut PathFollower), With<Enemy>>,
+) {
+    let path = match path {
+        Some(path) if path.is_changed() => path,
+        _ => return,
+    };
+    for (transform, mut follower) in query.iter_mut() {
+        let position = transform.translation / config.block_size;
+        let cell = IVec2::new(position.x.round() as i32, position.z.round() as i32);
+        // Enemies already on a cell that just got blocked keep their old route
+        if let Some(cells) = find_path(&surface, &rules, cell, path.goal) {
+            follower.waypoints = cells
+                .iter()
+                .filter_map(|cell| surface.world_position(*cell, config.block_size))
+                .collect();
+            follower.next = 0;
+        }
+    }
+}
+
+fn move_enemies(
+    mut commands: Commands,
+    time: Res<SimTime>,
+    mut events: EventWriter<EnemyReachedGoal>,
+    mut query: Query<(Entity, &mut Enemy, &mut Transform, &mut PathFollower)>,
+) {
+    let delta = time.delta_seconds();
+    for (entity, mut enemy, mut transform, mut follower) in query.iter_mut() {
+        let mut speed = enemy.speed;
+        if let Some(slowed) = &mut enemy.slowed {
+            speed *= slowed.factor;
+            slowed.remaining -= delta;
+            if slowed.remaining <= 0.0 {
+                enemy.slowed = None;
+            }
+        }
+
+        let mut step = speed * delta;
+        while step > 0.0 {
+            let target = match follower.waypoints.get(follower.next) {
+                Some(waypoint) => *waypoint + Vec3::Y * enemy.radius,
+                None => {
+                    events.send(EnemyReachedGoal {
+                        enemy: entity,
+                        damage: enemy.damage,
+                    });
+                    commands.entity(entity).despawn_recursive();
+                    break;
+                }
+            };
+            let to_target = target - transform.translation;
+            let distance = to_target.length();
+            if distance <= step {
+                transform.translation = target;
+                follower.next += 1;
+                follower.travelled += distance;
+                step -= distance;
+            } else {
+                transform.translation += to_target / distance * step;
+                follower.travelled += step;
+                step = 0.0;
+            }
+        }
+    }
+}
+
+/// Route of a single enemy, in world space
+#[derive(Component, Default)]
+pub struct PathFollower {
+    pub waypoints: Vec<Vec3>,
+    /// Index of the waypoint the enemy is walking towards
+    pub next: usize,
+    /// Distance walked since the enemy spawned
+    pub travelled: f32,
+}
+
+impl PathFollower {
+    /// Whether the enemy walked past its last waypoint. It is despawned at the end of the
+    /// simulation step, and must not be shot at or hit until then.
+    pub fn reached_goal(&self) -> bool {
+        self.next >= self.waypoints.len()
+    }
+}
+
+/// Temporarily multiplies the speed of an enemy
+#[derive(Clone, Copy, Debug)]
+pub struct Slowed {
+    pub factor: f32,
+    /// Seconds left
+    pub remaining: f32,
+}
+
+#[derive(Component, Clone, Debug)]
+pub struct Enemy {
+    pub kind: EnemyKind,
+    pub health: f32,
+    pub max_health: f32,
+    /// World units per second
+    pub speed: f32,
+    /// Gold awarded for killing the enemy
+    pub bounty: u32,
+    /// Lives lost when the enemy reaches the goal
+    pub damage: u32,
+    pub radius: f32,
+    pub wave: Option<usize>,
+    pub slowed: Option<Slowed>,
+}
+
+impl Enemy {
+    pub fn new(kind: EnemyKind) -> Self {
+        let (health, speed, bounty, damage, radius) = match kind {
+            EnemyKind::Grunt => (100.0, 6.0, 5, 1, 1.5),
+            EnemyKind::Runner => (60.0, 11.0, 6, 1, 1.0),
+            EnemyKind::Brute => (400.0, 3.5, 20, 3, 2.2),
+        };
+        Self {
+            kind,
+            health,
+            max_health: health,
+            speed,
+            bounty,
+            damage,
+            radius,
+            wave: None,
+            slowed: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize)]
+pub enum EnemyKind {
+    #[default]
+    Grunt,
+    Runner,
+    Brute,
+}
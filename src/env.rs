@@ -1,11 +1,12 @@
 use bevy::prelude::*;
 
+/// Starts loading the game data: towers and waves. Meshes and materials are loaded by the
+/// [`RenderingPlugin`](crate::rendering::RenderingPlugin).
 pub fn load_assets(
     asset_server: Res<AssetServer>,
     mut tower_assets: ResMut<super::tower::TowerAssets>,
-    mut materials: ResMut<Assets<StandardMaterial>>, // TODO: Remove
+    mut wave_assets: ResMut<super::wave::WaveAssets>,
 ) {
-    tower_assets.cannon_mesh = asset_server.load("models/basic_tower.glb#Mesh0/Primitive0");
-    tower_assets.body_mesh = asset_server.load("models/basic_tower.glb#Mesh1/Primitive0");
-    tower_assets.material = materials.add(Color::rgb(0.1, 0.2, 0.2).into());
+    tower_assets.catalogue = asset_server.load("towers/default.towers.ron");
+    wave_assets.schedule = asset_server.load("waves/default.waves.ron");
 }
@@ -0,0 +1,152 @@
+use crate::{
+    game_state::{GameState, StateScoped},
+    sim::{CommandSystem, PlayerCommand, PlayerCommands, SimulationApp},
+};
+use bevy::prelude::*;
+use leafwing_input_manager::{
+    plugin::InputManagerPlugin,
+    prelude::{ActionState, InputMap},
+    Actionlike, InputManagerBundle,
+};
+
+// https://github.com/Leafwing-Studios/leafwing-input-manager/blob/446ac84cfcd2c76ae5607cca1c871681af09a0d9/src/lib.rs#L98
+#[derive(Default)]
+pub struct GameSpeedPlugin {
+    desired_state: Option<GameState>,
+}
+
+impl GameSpeedPlugin {
+    pub fn new() -> Self {
+        Self {
+            desired_state: None,
+        }
+    }
+
+    pub fn run_in_state(state: GameState) -> Self {
+        Self {
+            desired_state: Some(state),
+        }
+    }
+}
+
+impl Plugin for GameSpeedPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameSpeed>();
+        if let Some(desired_state) = self.desired_state {
+            let p = InputManagerPlugin::<GameSpeedAction, GameState>::run_in_state(desired_state);
+            app.add_plugin(p)
+                .add_system_set(SystemSet::on_enter(desired_state).with_system(setup))
+                .add_system_set(SystemSet::on_update(desired_state).with_system(change_game_speed))
+                .add_command_system_set(
+                    SystemSet::new().with_system(apply_speed_commands.label(CommandSystem::Apply)),
+                );
+        } else {
+            panic!("GameSpeedPlugin::run_in_state() must be called with a GameState");
+        }
+    }
+}
+
+/// How fast the game runs compared to real time, by running more or fewer steps of the
+/// [`Simulation`](crate::sim::Simulation) every frame
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GameSpeed {
+    factor: f32,
+    /// Factor to go back to when unpausing
+    resume_factor: f32,
+}
+
+impl Default for GameSpeed {
+    fn default() -> Self {
+        Self {
+            factor: 1.0,
+            resume_factor: 1.0,
+        }
+    }
+}
+
+impl GameSpeed {
+    pub fn factor(&self) -> f32 {
+        self.factor
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.factor == 0.0
+    }
+
+    /// Runs the game `factor` times faster than real time, 0 stops it
+    pub fn set(&mut self, factor: f32) {
+        self.factor = factor.max(0.0);
+        if !self.is_paused() {
+            self.resume_factor = self.factor;
+        }
+    }
+
+    /// Stops the game, or brings it back to the speed it had before
+    pub fn toggle_pause(&mut self) {
+        if self.is_paused() {
+            self.factor = self.resume_factor;
+        } else {
+            self.factor = 0.0;
+        }
+    }
+}
+
+fn setup(mut commands: Commands, mut speed: ResMut<GameSpeed>) {
+    *speed = GameSpeed::default();
+    commands
+        .spawn_bundle(InputManagerBundle {
+            input_map: default_input_map(),
+            ..Default::default()
+        })
+        .insert(StateScoped(GameState::Defense));
+}
+
+fn change_game_speed(actions: Query<&ActionState<GameSpeedAction>>, mut speed: ResMut<GameSpeed>) {
+    for actions in actions.iter() {
+        for action in GameSpeedAction::ALL {
+            if !actions.just_pressed(&action) {
+                continue;
+            }
+            match action {
+                GameSpeedAction::TogglePause => speed.toggle_pause(),
+                GameSpeedAction::Normal => speed.set(1.0),
+                GameSpeedAction::Fast => speed.set(2.0),
+                GameSpeedAction::Fastest => speed.set(4.0),
+            }
+        }
+    }
+}
+
+/// Speed changes played back from a replay
+fn apply_speed_commands(commands: Res<PlayerCommands>, mut speed: ResMut<GameSpeed>) {
+    for command in commands.iter() {
+        if let PlayerCommand::SetSpeed(factor) = command {
+            speed.set(*factor);
+        }
+    }
+}
+
+fn default_input_map() -> InputMap<GameSpeedAction> {
+    let mut input_map: InputMap<GameSpeedAction> = InputMap::default();
+    input_map
+        .insert(GameSpeedAction::TogglePause, KeyCode::Space)
+        .insert(GameSpeedAction::Normal, KeyCode::Key1)
+        .insert(GameSpeedAction::Fast, KeyCode::Key2)
+        .insert(GameSpeedAction::Fastest, KeyCode::Key3);
+    input_map
+}
+
+#[derive(Actionlike, Debug, Clone, Hash, PartialEq, Eq, Copy)]
+pub enum GameSpeedAction {
+    TogglePause,
+    /// 1x
+    Normal,
+    /// 2x
+    Fast,
+    /// 4x
+    Fastest,
+}
+
+impl GameSpeedAction {
+    const ALL: [Self; 4] = [Self::TogglePause, Self::Normal, Self::Fast, Self::Fastest];
+}
@@ -1,10 +1,13 @@
-use bevy::prelude::{App, Plugin};
+use bevy::prelude::*;
 
 pub struct GameStatePlugin;
 
 impl Plugin for GameStatePlugin {
     fn build(&self, app: &mut App) {
         app.add_state(GameState::StartMenu);
+        for state in GameState::ALL {
+            app.add_system_set(SystemSet::on_exit(state).with_system(despawn_state_scoped));
+        }
     }
 }
 
@@ -12,5 +15,33 @@ impl Plugin for GameStatePlugin {
 pub enum GameState {
     StartMenu,
     Defense,
+    /// Pushed on top of [`GameState::Defense`], which is frozen but kept around
+    Paused,
     End,
 }
+
+impl GameState {
+    pub const ALL: [GameState; 4] = [
+        GameState::StartMenu,
+        GameState::Defense,
+        GameState::Paused,
+        GameState::End,
+    ];
+}
+
+/// Despawns the entity, and its children, when its state is exited. Pausing does not exit
+/// [`GameState::Defense`], its entities stay around until the game is left.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StateScoped(pub GameState);
+
+fn despawn_state_scoped(
+    mut commands: Commands,
+    state: Res<State<GameState>>,
+    query: Query<(Entity, &StateScoped)>,
+) {
+    for (entity, scope) in query.iter() {
+        if scope.0 == *state.current() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,381 @@
+use crate::{
+    economy::PlayerResources,
+    game_speed::GameSpeed,
+    game_state::{GameState, StateScoped},
+    map::ChunkMap,
+    pause_menu::PauseSystem,
+    sim::{PlayerCommand, PlayerCommands},
+    tower::{BuildSelection, TowerAssets, TowerCatalogue, TowerKind},
+    wave::WaveState,
+};
+use bevy::prelude::*;
+
+mod tower_panel;
+
+// https://github.com/Leafwing-Studios/leafwing-input-manager/blob/446ac84cfcd2c76ae5607cca1c871681af09a0d9/src/lib.rs#L98
+#[derive(Default)]
+pub struct HudPlugin {
+    desired_state: Option<GameState>,
+}
+
+impl HudPlugin {
+    pub fn new() -> Self {
+        Self {
+            desired_state: None,
+        }
+    }
+
+    pub fn run_in_state(state: GameState) -> Self {
+        Self {
+            desired_state: Some(state),
+        }
+    }
+}
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        if let Some(desired_state) = self.desired_state {
+            app.add_system_set(
+                SystemSet::on_enter(desired_state)
+                    .with_system(setup)
+                    .with_system(tower_panel::clear_selection),
+            )
+            .add_system_set(
+                SystemSet::on_update(desired_state)
+                    .with_system(update_stats)
+                    .with_system(select_tower_kind)
+                    .with_system(update_build_bar)
+                    .with_system(buy_chunk)
+                    .with_system(update_expand_button)
+                    .with_system(
+                        tower_panel::select_tower
+                            .label(HudSystem::SelectTower)
+                            .after(PauseSystem::Pause),
+                    )
+                    .with_system(tower_panel::spawn_panel.after(HudSystem::SelectTower))
+                    .with_system(tower_panel::update_panel)
+                    .with_system(tower_panel::panel_buttons)
+                    .with_system(tower_panel::update_range_ring.after(HudSystem::SelectTower)),
+            )
+            .add_system_set(
+                SystemSet::on_exit(desired_state).with_system(tower_panel::clear_selection),
+            );
+        } else {
+            panic!("HudPlugin::run_in_state() must be called with a GameState");
+        }
+    }
+}
+
+#[derive(SystemLabel, Clone, Hash, Debug, PartialEq, Eq)]
+enum HudSystem {
+    SelectTower,
+}
+
+const TEXT_COLOR: Color = Color::rgb(0.8, 0.8, 0.8);
+const UNAFFORDABLE_TEXT_COLOR: Color = Color::rgb(0.8, 0.3, 0.3);
+const BUTTON_COLOR: Color = Color::rgb(0.1, 0.1, 0.1);
+const HOVERED_BUTTON_COLOR: Color = Color::rgb(0.2, 0.2, 0.2);
+const SELECTED_BUTTON_COLOR: Color = Color::rgb(0.2, 0.3, 0.5);
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/FiraMono-Regular.ttf");
+    let text_style = TextStyle {
+        font,
+        font_size: 24.0,
+        color: TEXT_COLOR,
+    };
+
+    // NOTE: The ui's y axis points up, so `bottom` is the top of the screen and vice versa
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(0.),
+                    right: Val::Px(0.),
+                    bottom: Val::Px(0.),
+                    ..Default::default()
+                },
+                padding: Rect::all(Val::Px(8.)),
+                justify_content: JustifyContent::SpaceAround,
+                ..Default::default()
+            },
+            color: Color::rgba(0.1, 0.1, 0.1, 0.7).into(),
+            ..Default::default()
+        })
+        .insert(StateScoped(GameState::Defense))
+        .insert(Name::new("hud:stats"))
+        .with_children(|parent| {
+            for stat in [
+                HudStat::Gold,
+                HudStat::Lives,
+                HudStat::Score,
+                HudStat::Wave,
+                HudStat::Speed,
+            ] {
+                parent
+                    .spawn_bundle(TextBundle {
+                        text: Text::with_section(
+                            String::new(),
+                            text_style.clone(),
+                            Default::default(),
+                        ),
+                        ..Default::default()
+                    })
+                    .insert(stat);
+            }
+        });
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(0.),
+                    right: Val::Px(0.),
+                    top: Val::Px(8.),
+                    ..Default::default()
+                },
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .insert(StateScoped(GameState::Defense))
+        .insert(Name::new("hud:build_bar"))
+        .with_children(|parent| {
+            for kind in TowerKind::ALL {
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            min_size: Size::new(Val::Px(140.), Val::Px(56.)),
+                            margin: Rect::all(Val::Px(4.)),
+                            padding: Rect::all(Val::Px(8.)),
+                            align_items: AlignItems::Center,
+                            justify_content: JustifyContent::Center,
+                            ..Default::default()
+                        },
+                        color: BUTTON_COLOR.into(),
+                        ..Default::default()
+                    })
+                    .insert(Name::new(format!("button:build:{:?}", kind)))
+                    .insert(BuildButton(kind))
+                    .with_children(|parent| {
+                        parent
+                            .spawn_bundle(TextBundle {
+                                text: Text::with_section(
+                                    format!("{:?}", kind),
+                                    TextStyle {
+                                        font_size: 20.0,
+                                        ..text_style.clone()
+                                    },
+                                    TextAlignment {
+                                        vertical: VerticalAlign::Center,
+                                        horizontal: HorizontalAlign::Center,
+                                    },
+                                ),
+                                ..Default::default()
+                            })
+                            .insert(BuildButton(kind));
+                    });
+            }
+
+            parent
+                .spawn_bundle(ButtonBundle {
+                    style: Style {
+                        min_size: Size::new(Val::Px(140.), Val::Px(56.)),
+                        margin: Rect::all(Val::Px(4.)),
+                        padding: Rect::all(Val::Px(8.)),
+                        align_items: AlignItems::Center,
+                        justify_content: JustifyContent::Center,
+                        ..Default::default()
+                    },
+                    color: BUTTON_COLOR.into(),
+                    ..Default::default()
+                })
+                .insert(Name::new("button:expand"))
+                .insert(ExpandButton)
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(TextBundle {
+                            text: Text::with_section(
+                                "Expand",
+                                TextStyle {
+                                    font_size: 20.0,
+                                    ..text_style.clone()
+                                },
+                                TextAlignment {
+                                    vertical: VerticalAlign::Center,
+                                    horizontal: HorizontalAlign::Center,
+                                },
+                            ),
+                            ..Default::default()
+                        })
+                        .insert(ExpandButton);
+                });
+        });
+}
+
+fn update_stats(
+    resources: Res<PlayerResources>,
+    wave_state: Res<WaveState>,
+    speed: Res<GameSpeed>,
+    mut query: Query<(&HudStat, &mut Text)>,
+) {
+    for (stat, mut text) in query.iter_mut() {
+        let value = match stat {
+            HudStat::Gold => format!("Gold: {}", resources.gold),
+            HudStat::Lives => format!("Lives: {}", resources.lives),
+            HudStat::Score => format!("Score: {}", resources.score),
+            HudStat::Wave => wave_text(&wave_state),
+            HudStat::Speed if speed.is_paused() => "Paused [Space]".to_string(),
+            HudStat::Speed => format!("Speed: {}x", speed.factor()),
+        };
+        // Only touch the text when it changes, so that it is not laid out again every frame
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
+
+fn wave_text(wave_state: &WaveState) -> String {
+    if wave_state.total == 0 {
+        return "Wave -".to_string();
+    }
+    let mut text = format!("Wave {}/{}", wave_state.next, wave_state.total);
+    if let Some(countdown) = &wave_state.countdown {
+        if wave_state.next < wave_state.total {
+            let left = countdown.duration().saturating_sub(countdown.elapsed());
+            text += &format!(" - next in {}s [N]", left.as_secs_f32().ceil());
+        }
+    }
+    text
+}
+
+#[allow(clippy::type_complexity)]
+fn select_tower_kind(
+    mut selection: ResMut<BuildSelection>,
+    query: Query<(&Interaction, &BuildButton), (Changed<Interaction>, With<Button>)>,
+) {
+    for (interaction, BuildButton(kind)) in query.iter() {
+        if *interaction == Interaction::Clicked && selection.kind != *kind {
+            selection.kind = *kind;
+        }
+    }
+}
+
+/// Highlights the selected kind of tower and shows the name and cost of every kind, in red when
+/// it can not be afforded
+fn update_build_bar(
+    selection: Res<BuildSelection>,
+    resources: Res<PlayerResources>,
+    tower_assets: Res<TowerAssets>,
+    catalogues: Res<Assets<TowerCatalogue>>,
+    mut buttons: Query<(&Interaction, &BuildButton, &mut UiColor), With<Button>>,
+    mut texts: Query<(&BuildButton, &mut Text)>,
+) {
+    for (interaction, BuildButton(kind), mut color) in buttons.iter_mut() {
+        let new_color = if selection.kind == *kind {
+            SELECTED_BUTTON_COLOR
+        } else if *interaction == Interaction::Hovered {
+            HOVERED_BUTTON_COLOR
+        } else {
+            BUTTON_COLOR
+        };
+        if color.0 != new_color {
+            color.0 = new_color;
+        }
+    }
+
+    for (BuildButton(kind), mut text) in texts.iter_mut() {
+        let (value, text_color) = match tower_assets.spec(&catalogues, *kind) {
+            Some(spec) => (
+                format!("{} {}g", spec.name, spec.cost),
+                if resources.can_afford(spec.cost) {
+                    TEXT_COLOR
+                } else {
+                    UNAFFORDABLE_TEXT_COLOR
+                },
+            ),
+            None => (format!("{:?}", kind), TEXT_COLOR),
+        };
+        let section = &text.sections[0];
+        if section.value != value || section.style.color != text_color {
+            let section = &mut text.sections[0];
+            section.value = value;
+            section.style.color = text_color;
+        }
+    }
+}
+
+/// Asks for the chunk next to the map that is closest to its first one
+#[allow(clippy::type_complexity)]
+fn buy_chunk(
+    chunk_map: Res<ChunkMap>,
+    mut commands: ResMut<PlayerCommands>,
+    query: Query<&Interaction, (Changed<Interaction>, With<Button>, With<ExpandButton>)>,
+) {
+    for interaction in query.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        if let Some(coord) = chunk_map.next_expansion() {
+            commands.push(PlayerCommand::Expand {
+                chunk: (coord.x, coord.y),
+            });
+        }
+    }
+}
+
+/// Shows the cost of the next chunk, in red when it can not be afforded
+fn update_expand_button(
+    chunk_map: Res<ChunkMap>,
+    resources: Res<PlayerResources>,
+    mut buttons: Query<(&Interaction, &mut UiColor), (With<Button>, With<ExpandButton>)>,
+    mut texts: Query<&mut Text, With<ExpandButton>>,
+) {
+    for (interaction, mut color) in buttons.iter_mut() {
+        let new_color = if *interaction == Interaction::Hovered {
+            HOVERED_BUTTON_COLOR
+        } else {
+            BUTTON_COLOR
+        };
+        if color.0 != new_color {
+            color.0 = new_color;
+        }
+    }
+
+    let cost = chunk_map.expansion_cost();
+    let value = format!("Expand {}g", cost);
+    let text_color = if resources.can_afford(cost) {
+        TEXT_COLOR
+    } else {
+        UNAFFORDABLE_TEXT_COLOR
+    };
+    for mut text in texts.iter_mut() {
+        let section = &text.sections[0];
+        if section.value != value || section.style.color != text_color {
+            let section = &mut text.sections[0];
+            section.value = value.clone();
+            section.style.color = text_color;
+        }
+    }
+}
+
+#[derive(Component)]
+enum HudStat {
+    Gold,
+    Lives,
+    Score,
+    Wave,
+    Speed,
+}
+
+/// Button of the build bar, and its label
+#[derive(Component, Clone, Copy)]
+struct BuildButton(TowerKind);
+
+/// Button of the build bar that buys the next chunk of the map, and its label
+#[derive(Component, Clone, Copy)]
+struct ExpandButton;
@@ -0,0 +1,318 @@
+use super::{BUTTON_COLOR, HOVERED_BUTTON_COLOR, SELECTED_BUTTON_COLOR, TEXT_COLOR};
+use crate::{
+    game_state::{GameState, StateScoped},
+    map::MapConfig,
+    rendering::TowerRenderAssets,
+    sim::{PlayerCommand, PlayerCommands},
+    tower::{
+        SelectedTower, TargetPriority, Tower, TowerAssets, TowerBody, TowerCannon, TowerCatalogue,
+        TowerSold, TowerUpgraded,
+    },
+};
+use bevy::prelude::*;
+use bevy_mod_picking::PickingEvent;
+
+/// Selects the tower a clicked part belongs to, clicking anything else clears the selection
+#[allow(clippy::type_complexity)]
+pub(super) fn select_tower(
+    mut events: EventReader<PickingEvent>,
+    keys: Res<Input<KeyCode>>,
+    mut selected: ResMut<SelectedTower>,
+    mut sold: EventReader<TowerSold>,
+    parts: Query<&Parent, Or<(With<TowerBody>, With<TowerCannon>)>>,
+    towers: Query<(), With<Tower>>,
+) {
+    let mut selection = selected.tower;
+    for event in events.iter() {
+        if let PickingEvent::Clicked(e) = event {
+            selection = parts.get(*e).ok().map(|parent| parent.0);
+        }
+    }
+    if keys.just_pressed(KeyCode::Escape)
+        || sold.iter().any(|e| Some(e.tower) == selection)
+        || matches!(selection, Some(tower) if towers.get(tower).is_err())
+    {
+        selection = None;
+    }
+    if selected.tower != selection {
+        selected.tower = selection;
+    }
+}
+
+pub(super) fn clear_selection(mut selected: ResMut<SelectedTower>) {
+    selected.tower = None;
+}
+
+/// Spawns the panel of the selected tower, and spawns it again whenever its upgrades change
+#[allow(clippy::too_many_arguments)]
+pub(super) fn spawn_panel(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    selected: Res<SelectedTower>,
+    mut upgraded: EventReader<TowerUpgraded>,
+    tower_assets: Res<TowerAssets>,
+    catalogues: Res<Assets<TowerCatalogue>>,
+    towers: Query<&Tower>,
+    panels: Query<Entity, With<TowerPanel>>,
+) {
+    let upgraded = upgraded.iter().any(|e| Some(e.tower) == selected.tower);
+    if !selected.is_changed() && !upgraded {
+        return;
+    }
+    panels.for_each(|e| commands.entity(e).despawn_recursive());
+
+    let (tower, spec) = match selected.tower.and_then(|tower| {
+        let tower = towers.get(tower).ok()?;
+        Some((tower, tower_assets.spec(&catalogues, tower.kind)?))
+    }) {
+        Some(selection) => selection,
+        None => return,
+    };
+
+    let font = asset_server.load("fonts/FiraMono-Regular.ttf");
+    let text_style = TextStyle {
+        font,
+        font_size: 18.0,
+        color: TEXT_COLOR,
+    };
+    let text = |value: String, size: f32| TextBundle {
+        text: Text::with_section(
+            value,
+            TextStyle {
+                font_size: size,
+                ..text_style.clone()
+            },
+            Default::default(),
+        ),
+        style: Style {
+            margin: Rect::all(Val::Px(4.)),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let button = |width: f32| ButtonBundle {
+        style: Style {
+            min_size: Size::new(Val::Px(width), Val::Px(32.)),
+            margin: Rect::all(Val::Px(2.)),
+            padding: Rect::all(Val::Px(4.)),
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            ..Default::default()
+        },
+        color: BUTTON_COLOR.into(),
+        ..Default::default()
+    };
+    let row = || NodeBundle {
+        style: Style {
+            flex_wrap: FlexWrap::Wrap,
+            justify_content: JustifyContent::Center,
+            ..Default::default()
+        },
+        color: Color::NONE.into(),
+        ..Default::default()
+    };
+
+    let title = match tower.upgrade_path.and_then(|p| spec.upgrades.get(p)) {
+        Some(path) => format!("{} - {} {}", spec.name, path.name, tower.tier),
+        None => spec.name.clone(),
+    };
+
+    // NOTE: The ui's y axis points up, `bottom` is the top of the screen and columns are reversed
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(8.),
+                    bottom: Val::Px(64.),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Px(280.), Val::Auto),
+                padding: Rect::all(Val::Px(8.)),
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Stretch,
+                ..Default::default()
+            },
+            color: Color::rgba(0.1, 0.1, 0.1, 0.8).into(),
+            ..Default::default()
+        })
+        .insert(TowerPanel {})
+        .insert(StateScoped(GameState::Defense))
+        .insert(Name::new("hud:tower_panel"))
+        .with_children(|parent| {
+            parent.spawn_bundle(text(title, 24.0));
+            parent
+                .spawn_bundle(text(String::new(), 18.0))
+                .insert(TowerStatsText {});
+
+            parent.spawn_bundle(text("Target".to_string(), 18.0));
+            parent.spawn_bundle(row()).with_children(|parent| {
+                for priority in TargetPriority::ALL {
+                    parent
+                        .spawn_bundle(button(120.))
+                        .insert(PanelButton::Priority(priority))
+                        .with_children(|parent| {
+                            parent.spawn_bundle(text(format!("{:?}", priority), 16.0));
+                        });
+                }
+            });
+
+            parent.spawn_bundle(text("Upgrades".to_string(), 18.0));
+            for (index, path) in spec.upgrades.iter().enumerate() {
+                let label = match tower.next_tier(spec, index) {
+                    Some(tier) => format!("{} {} ({}g)", path.name, tower.tier + 1, tier.cost),
+                    None if tower.upgrade_path == Some(index) => format!("{} (max)", path.name),
+                    None => format!("{} (locked)", path.name),
+                };
+                parent
+                    .spawn_bundle(button(240.))
+                    .insert(PanelButton::Upgrade(index))
+                    .with_children(|parent| {
+                        parent.spawn_bundle(text(label, 16.0));
+                    });
+            }
+
+            parent
+                .spawn_bundle(button(240.))
+                .insert(PanelButton::Sell)
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(text(String::new(), 16.0))
+                        .insert(SellText {});
+                });
+        });
+}
+
+/// Keeps the stats, sell value and target priority of the panel up to date
+#[allow(clippy::type_complexity)]
+pub(super) fn update_panel(
+    selected: Res<SelectedTower>,
+    towers: Query<&Tower>,
+    mut stats: Query<&mut Text, (With<TowerStatsText>, Without<SellText>)>,
+    mut sell: Query<&mut Text, (With<SellText>, Without<TowerStatsText>)>,
+    mut buttons: Query<(&Interaction, &PanelButton, &mut UiColor)>,
+) {
+    let tower = match selected.tower.and_then(|tower| towers.get(tower).ok()) {
+        Some(tower) => tower,
+        None => return,
+    };
+
+    let value = format!(
+        "Kills: {}\nDamage dealt: {:.0}\nRange: {:.0}\nDamage: {:.0}\nFire rate: {:.1}/s",
+        tower.kills, tower.damage_dealt, tower.range, tower.damage, tower.fire_rate
+    );
+    for mut text in stats.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+    let value = format!("Sell (+{}g)", tower.sell_value());
+    for mut text in sell.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+
+    for (interaction, button, mut color) in buttons.iter_mut() {
+        let new_color = match (button, interaction) {
+            (PanelButton::Priority(priority), _) if *priority == tower.priority => {
+                SELECTED_BUTTON_COLOR
+            }
+            (_, Interaction::Hovered) => HOVERED_BUTTON_COLOR,
+            _ => BUTTON_COLOR,
+        };
+        if color.0 != new_color {
+            color.0 = new_color;
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub(super) fn panel_buttons(
+    selected: Res<SelectedTower>,
+    config: Res<MapConfig>,
+    mut commands: ResMut<PlayerCommands>,
+    towers: Query<&Transform, With<Tower>>,
+    query: Query<(&Interaction, &PanelButton), (Changed<Interaction>, With<Button>)>,
+) {
+    let cell = match selected.tower.and_then(|t| towers.get(t).ok()) {
+        Some(transform) => config.cell_at(transform.translation),
+        None => return,
+    };
+    let cell = (cell.x, cell.y);
+    for (interaction, button) in query.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        commands.push(match button {
+            PanelButton::Priority(priority) => PlayerCommand::SetPriority {
+                cell,
+                priority: *priority,
+            },
+            PanelButton::Upgrade(path) => PlayerCommand::Upgrade { cell, path: *path },
+            PanelButton::Sell => PlayerCommand::Sell { cell },
+        });
+    }
+}
+
+/// Shows the range of the selected tower as a ring on the ground
+#[allow(clippy::type_complexity)]
+pub(super) fn update_range_ring(
+    mut commands: Commands,
+    selected: Res<SelectedTower>,
+    config: Res<MapConfig>,
+    render_assets: Res<TowerRenderAssets>,
+    towers: Query<(&Tower, &GlobalTransform)>,
+    mut rings: Query<(Entity, &mut Transform), With<RangeRing>>,
+) {
+    let (tower, tower_transform) = match selected.tower.and_then(|t| towers.get(t).ok()) {
+        Some(tower) => tower,
+        None => {
+            rings.for_each_mut(|(e, _)| commands.entity(e).despawn_recursive());
+            return;
+        }
+    };
+    let transform = Transform::from_translation(
+        tower_transform.translation + Vec3::Y * (config.block_size / 2.0 + 0.1),
+    )
+    .with_scale(Vec3::new(tower.range, 1.0, tower.range));
+
+    match rings.iter_mut().next() {
+        Some((_, mut ring)) => {
+            if *ring != transform {
+                *ring = transform;
+            }
+        }
+        None => {
+            commands
+                .spawn_bundle(PbrBundle {
+                    mesh: render_assets.range_mesh.clone(),
+                    material: render_assets.range_material.clone(),
+                    transform,
+                    ..Default::default()
+                })
+                .insert(RangeRing {})
+                .insert(StateScoped(GameState::Defense))
+                .insert(Name::new("range_ring"));
+        }
+    }
+}
+
+#[derive(Component)]
+pub(super) struct TowerPanel {}
+
+#[derive(Component)]
+pub(super) struct TowerStatsText {}
+
+#[derive(Component)]
+pub(super) struct SellText {}
+
+#[derive(Component)]
+pub(super) struct RangeRing {}
+
+#[derive(Component, Clone, Copy)]
+pub(super) enum PanelButton {
+    Priority(TargetPriority),
+    Upgrade(usize),
+    Sell,
+}
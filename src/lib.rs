@@ -3,8 +3,23 @@
 #![allow(unused_variables)]
 
 pub mod camera;
+pub mod economy;
+pub mod end_screen;
+pub mod enemy;
 pub mod env;
+pub mod game_speed;
 pub mod game_state;
+pub mod hud;
 pub mod map;
+pub mod pause_menu;
+pub mod projectile;
+pub mod rendering;
+pub mod replay;
+pub mod save;
+pub mod settings;
+pub mod sim;
 pub mod start_menu;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
 pub mod tower;
+pub mod wave;
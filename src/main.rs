@@ -34,9 +34,39 @@ fn main() {
         ))
         //.add_plugin(yatd_lib::camera::CameraPlugin::new())
         .add_plugin(yatd_lib::map::MapPlugin::run_in_state(GameState::Defense))
+        .add_plugin(yatd_lib::sim::SimulationPlugin::run_in_state(
+            GameState::Defense,
+        ))
+        .add_plugin(yatd_lib::game_speed::GameSpeedPlugin::run_in_state(
+            GameState::Defense,
+        ))
         .add_plugin(yatd_lib::tower::TowerPlugin::run_in_state(
             GameState::Defense,
         ))
+        .add_plugin(yatd_lib::enemy::EnemyPlugin::run_in_state(
+            GameState::Defense,
+        ))
+        .add_plugin(yatd_lib::projectile::ProjectilePlugin::run_in_state(
+            GameState::Defense,
+        ))
+        .add_plugin(yatd_lib::wave::WavePlugin::run_in_state(GameState::Defense))
+        .add_plugin(yatd_lib::economy::EconomyPlugin::run_in_state(
+            GameState::Defense,
+        ))
+        .add_plugin(yatd_lib::rendering::RenderingPlugin::run_in_state(
+            GameState::Defense,
+        ))
+        .add_plugin(yatd_lib::hud::HudPlugin::run_in_state(GameState::Defense))
+        .add_plugin(yatd_lib::save::SavePlugin::run_in_state(GameState::Defense))
+        .add_plugin(yatd_lib::replay::ReplayPlugin::run_in_state(
+            GameState::Defense,
+        ))
+        .add_plugin(yatd_lib::pause_menu::PauseMenuPlugin::run_in_state(
+            GameState::Paused,
+        ))
+        .add_plugin(yatd_lib::end_screen::EndScreenPlugin::run_in_state(
+            GameState::End,
+        ))
         .run();
 }
 
@@ -45,6 +75,9 @@ fn setup(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
+    // Shared by the ui of every state
+    commands.spawn_bundle(UiCameraBundle::default());
+
     // plane
     commands.spawn_bundle(PbrBundle {
         mesh: meshes.add(Mesh::from(shape::Plane { size: 200.0 })),
@@ -1,6 +1,22 @@
-use crate::{game_state::GameState, tower::TowerAssets};
+use crate::{
+    economy::PlayerResources,
+    game_state::{GameState, StateScoped},
+    pause_menu::PauseSystem,
+    sim::{CommandSystem, PlayerCommand, PlayerCommands, SimulationApp},
+    tower::{BuildSelection, Tower, TowerAssets, TowerCatalogue, TowerKind, TowerSold},
+};
 use bevy::prelude::*;
-use bevy_mod_picking::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{collections::HashMap, fmt};
+
+mod path;
+mod pathfinding;
+mod placement;
+mod terrain;
+pub use path::{generate_path, validate_path, Column, EnemyPath, Surface};
+pub use pathfinding::{find_path, find_path_avoiding, PathRules};
+pub use placement::{check_placement, PlacementError, PlacementPreview};
+pub use terrain::TerrainGenerator;
 
 // https://github.com/Leafwing-Studios/leafwing-input-manager/blob/446ac84cfcd2c76ae5607cca1c871681af09a0d9/src/lib.rs#L98
 #[derive(Default)]
@@ -24,27 +40,56 @@ impl MapPlugin {
 
 impl Plugin for MapPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(DefaultPickingPlugins);
+        if !app.world.contains_resource::<MapSeed>() {
+            let seed = MapSeed::random();
+            app.insert_resource(seed)
+                .insert_resource(RolledSeed(seed.0));
+        }
+        app.init_resource::<MapConfig>()
+            .init_resource::<ChunkMap>()
+            .init_resource::<Surface>()
+            .init_resource::<PathRules>()
+            .init_resource::<PlacementPreview>()
+            .add_event::<ExpandMap>()
+            .add_event::<BlockClicked>();
+        if !app.world.contains_resource::<ActiveMapGenerator>() {
+            app.insert_resource(ActiveMapGenerator(Box::new(TerrainGenerator::default())));
+        }
 
         if let Some(desired_state) = self.desired_state {
-            app.insert_resource(PickingPluginsState {
-                enable_picking: false,
-                ..Default::default()
-            });
-            app.add_system_set_to_stage(
-                CoreStage::PreUpdate,
-                SystemSet::new().with_system(pick_block),
+            app.add_system_set(
+                SystemSet::on_update(desired_state)
+                    .with_system(expand_map)
+                    .with_system(pick_block)
+                    .with_system(placement::cancel_placement.after(PauseSystem::Pause)),
+            );
+            // Towers change the route of the enemies, which must change at the same step every
+            // time the game is played
+            app.add_command_system_set(
+                SystemSet::new()
+                    .with_system(build_towers.label(CommandSystem::Apply))
+                    .with_system(buy_chunks.label(CommandSystem::Apply))
+                    .with_system(
+                        free_sold_blocks
+                            .after(CommandSystem::Apply)
+                            .before(MapSystem::SyncSurface),
+                    )
+                    .with_system(
+                        sync_blocked_cells
+                            .label(MapSystem::SyncSurface)
+                            .after(CommandSystem::Apply),
+                    )
+                    .with_system(repath.after(MapSystem::SyncSurface)),
             );
-            // app.add_system_set(SystemSet::on_update(desired_state).with_system(pick_block));
             app.add_system_set(
                 SystemSet::on_enter(desired_state)
-                    .with_system(setup)
-                    .with_system(enable_picking),
+                    .with_system(setup.label(MapSystem::Setup))
+                    .with_system(spawn_seed_label.after(MapSystem::Setup)),
             )
             .add_system_set(
                 SystemSet::on_exit(desired_state)
-                    .with_system(disable_picking)
-                    .with_system(destroy),
+                    .with_system(clear_map)
+                    .with_system(placement::reset_preview),
             );
         } else {
             panic!("MapPlugin::run_in_state() must be called with a GameState");
@@ -52,87 +97,255 @@ impl Plugin for MapPlugin {
     }
 }
 
-fn enable_picking(
-    mut state: ResMut<PickingPluginsState>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    mut picking_materials: ResMut<
-        MeshButtonMaterials<StandardMaterial, StandardMaterialPickingColors>,
-    >,
+/// Spawns the first chunk of the map. A [`RolledSeed`] whose map leaves the enemies no route from
+/// spawn to goal is replaced by the seeds after it, a seed the player chose sends the game back to
+/// the start menu with a [`MapError`] instead.
+#[allow(clippy::too_many_arguments)]
+fn setup(
+    mut commands: Commands,
+    mut chunk_map: ResMut<ChunkMap>,
+    mut surface: ResMut<Surface>,
+    mut state: ResMut<State<GameState>>,
+    mut seed: ResMut<MapSeed>,
+    mut rolled: Option<ResMut<RolledSeed>>,
+    config: Res<MapConfig>,
+    rules: Res<PathRules>,
+    generator: Res<ActiveMapGenerator>,
 ) {
-    state.enable_picking = true;
-    picking_materials.pressed = materials.add(Color::rgba(1.0, 0.0, 0.0, 1.0).into());
-    //    picking_materials.pressed = materials.add(Color::rgba(0.0, 0.0, 0.0, 0.0).into());
-    dbg!(state.enable_picking);
+    let coord = IVec2::ZERO;
+    let mut rerolls = 0;
+    let (layout, path) = loop {
+        let mut rng = seed.rng();
+        let layout = generator.0.generate(
+            &mut rng,
+            coord,
+            config.chunk_length,
+            config.chunk_width,
+            config.chunk_height,
+        );
+        surface.clear();
+        surface.insert_chunk(coord, &layout);
+
+        let path = match config.path_endpoints {
+            Some((spawn, goal)) => validate_path(&surface, &rules, spawn, goal),
+            None => generate_path(
+                &surface,
+                &rules,
+                coord,
+                layout.length,
+                layout.width,
+                &mut rng,
+            ),
+        };
+        match (path, &mut rolled) {
+            (Some(path), _) => break (layout, path),
+            (None, Some(rolled)) if rolled.0 == seed.0 && rerolls < MAX_REROLLS => {
+                info!(
+                    "The map of seed {} has no enemy path, trying the next seed",
+                    seed.0
+                );
+                *seed = seed.next();
+                rolled.0 = seed.0;
+                rerolls += 1;
+            }
+            (None, _) => {
+                let error = MapError { seed: seed.0 };
+                warn!("{}", error);
+                commands.insert_resource(error);
+                if let Err(e) = state.overwrite_set(GameState::StartMenu) {
+                    warn!("Could not leave the game: {}", e);
+                }
+                return;
+            }
+        }
+    };
+
+    commands.remove_resource::<MapError>();
+    commands.insert_resource(path);
+    let chunk = spawn_chunk(&mut commands, coord, &layout, config.block_size);
+    chunk_map.chunks.insert(coord, chunk);
 }
 
-fn disable_picking(mut state: ResMut<PickingPluginsState>) {
-    state.enable_picking = false;
+/// Spawns the chunks requested through [`ExpandMap`] events
+fn expand_map(
+    mut commands: Commands,
+    mut chunk_map: ResMut<ChunkMap>,
+    mut surface: ResMut<Surface>,
+    mut events: EventReader<ExpandMap>,
+    config: Res<MapConfig>,
+    seed: Res<MapSeed>,
+    generator: Res<ActiveMapGenerator>,
+) {
+    for ExpandMap { coord } in events.iter() {
+        if !chunk_map.can_expand_to(*coord) {
+            warn!("Cannot expand the map to chunk {}", coord);
+            continue;
+        }
+        spawn_expansion(
+            &mut commands,
+            &mut chunk_map,
+            &mut surface,
+            *coord,
+            &config,
+            &seed,
+            &generator,
+        );
+    }
 }
 
-fn setup(
-    commands: Commands,
-    meshes: ResMut<Assets<Mesh>>,
-    materials: ResMut<Assets<StandardMaterial>>,
+/// Spawns the chunks bought with [`PlayerCommand::Expand`]s, if the map can grow there and the
+/// player can afford them
+#[allow(clippy::too_many_arguments)]
+fn buy_chunks(
+    mut commands: Commands,
+    player_commands: Res<PlayerCommands>,
+    mut chunk_map: ResMut<ChunkMap>,
+    mut surface: ResMut<Surface>,
+    mut resources: ResMut<PlayerResources>,
+    config: Res<MapConfig>,
+    seed: Res<MapSeed>,
+    generator: Res<ActiveMapGenerator>,
+) {
+    for command in player_commands.iter() {
+        let coord = match command {
+            PlayerCommand::Expand { chunk } => IVec2::new(chunk.0, chunk.1),
+            _ => continue,
+        };
+        if !chunk_map.can_expand_to(coord) {
+            info!("Can not expand the map to chunk {}", coord);
+            continue;
+        }
+        let cost = chunk_map.expansion_cost();
+        if !resources.spend(cost) {
+            info!("Can not afford chunk {} for {} gold", coord, cost);
+            continue;
+        }
+        spawn_expansion(
+            &mut commands,
+            &mut chunk_map,
+            &mut surface,
+            coord,
+            &config,
+            &seed,
+            &generator,
+        );
+    }
+}
+
+/// Generates the chunk at `coord` from the seed and spawns it, the caller checks that the map can
+/// be expanded there
+fn spawn_expansion(
+    commands: &mut Commands,
+    chunk_map: &mut ChunkMap,
+    surface: &mut Surface,
+    coord: IVec2,
+    config: &MapConfig,
+    seed: &MapSeed,
+    generator: &ActiveMapGenerator,
 ) {
-    spawn_chunk(commands, meshes, materials, 10, 10, 4, 5.0);
+    let layout = generator.0.generate(
+        &mut seed.rng(),
+        coord,
+        config.chunk_length,
+        config.chunk_width,
+        config.chunk_height,
+    );
+    surface.insert_chunk(coord, &layout);
+    let chunk = spawn_chunk(commands, coord, &layout, config.block_size);
+    chunk_map.chunks.insert(coord, chunk);
 }
 
-fn destroy(mut commands: Commands, query: Query<Entity, With<Chunk>>) {
-    commands.entity(query.single()).despawn_recursive();
+fn spawn_seed_label(mut commands: Commands, asset_server: Res<AssetServer>, seed: Res<MapSeed>) {
+    let font = asset_server.load("fonts/FiraMono-Regular.ttf");
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                // The ui's y axis points up, `top` is the bottom of the screen
+                position: Rect {
+                    left: Val::Px(8.),
+                    top: Val::Px(8.),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                format!("Seed: {}", seed.0),
+                TextStyle {
+                    font,
+                    font_size: 20.0,
+                    color: Color::rgb(0.8, 0.8, 0.8),
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(StateScoped(GameState::Defense))
+        .insert(Name::new("seed_label"));
 }
 
-fn spawn_chunk(
+/// Forgets the map, its chunks are despawned as they are scoped to [`GameState::Defense`]
+fn clear_map(
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    length: usize,
-    width: usize,
-    height: usize,
-    block_size: f32,
+    mut chunk_map: ResMut<ChunkMap>,
+    mut surface: ResMut<Surface>,
 ) {
+    chunk_map.chunks.clear();
+    surface.clear();
+    commands.remove_resource::<EnemyPath>();
+}
+
+/// Spawns a chunk and its blocks. They only hold game data, their meshes are added by the
+/// [`RenderingPlugin`](crate::rendering::RenderingPlugin).
+fn spawn_chunk(
+    commands: &mut Commands,
+    coord: IVec2,
+    layout: &ChunkLayout,
+    block_size: f32,
+) -> Entity {
     commands
         .spawn_bundle(ChunkBundle {
             properties: Chunk {
-                length,
-                width,
-                height,
+                coord,
+                length: layout.length,
+                width: layout.width,
+                height: layout.height,
             },
+            transform: Transform::from_translation(Vec3::new(
+                (coord.x * layout.length as i32) as f32 * block_size,
+                0.0,
+                (coord.y * layout.width as i32) as f32 * block_size,
+            )),
             ..Default::default()
         })
+        .insert(StateScoped(GameState::Defense))
+        .insert(Name::new(format!("chunk:{}", coord)))
         .with_children(|p| {
-            for l in 0..length {
-                for w in 0..width {
-                    let height = rand::random::<usize>() % (height - 1) + 1;
-                    for h in 0..height {
-                        let mut block = p.spawn_bundle(BlockBundle {
+            for l in 0..layout.length {
+                for w in 0..layout.width {
+                    for (h, kind) in layout.column(l, w).iter().enumerate() {
+                        p.spawn_bundle(BlockBundle {
                             properties: Block {
+                                chunk: coord,
                                 x: l,
                                 y: h,
                                 z: w,
-                                kind: BlockKind::Stone,
+                                kind: *kind,
                                 has_tower: false,
+                                tower: None,
                             },
-                            pbr: PbrBundle {
-                                mesh: meshes.add(Mesh::from(shape::Cube { size: block_size })),
-                                material: materials.add(
-                                    Color::rgb(l as f32 / 10.0, h as f32 / 10.0, w as f32 / 10.0)
-                                        .into(),
-                                ),
-                                transform: Transform::from_translation(Vec3::new(
-                                    l as f32 * block_size,
-                                    h as f32 * block_size,
-                                    w as f32 * block_size,
-                                )),
-                                ..Default::default()
-                            },
+                            transform: Transform::from_translation(Vec3::new(
+                                l as f32 * block_size,
+                                h as f32 * block_size,
+                                w as f32 * block_size,
+                            )),
+                            global_transform: GlobalTransform::default(),
                         });
-                        if h == height - 1 {
-                            block.insert_bundle(PickableBundle::default());
-                        }
                     }
                 }
             }
-        });
+        })
+        .id()
 }
 
 #[derive(Bundle, Default)]
@@ -144,6 +357,8 @@ pub struct ChunkBundle {
 
 #[derive(Component, Default)]
 pub struct Chunk {
+    /// Position in the chunk grid, in chunks rather than blocks
+    pub coord: IVec2,
     pub length: usize,
     pub width: usize,
     pub height: usize,
@@ -152,58 +367,513 @@ pub struct Chunk {
 #[derive(Bundle)]
 pub struct BlockBundle {
     pub properties: Block,
-    #[bundle]
-    pub pbr: PbrBundle,
+    pub transform: Transform,
+    pub global_transform: GlobalTransform,
 }
 
 #[derive(Component)]
 pub struct Block {
+    chunk: IVec2,
     x: usize,
     y: usize,
     z: usize,
     kind: BlockKind,
     has_tower: bool,
+    /// Tower standing on the block
+    tower: Option<Entity>,
 }
 
+impl Block {
+    /// World cell of the column this block belongs to, see [`Surface`]
+    pub fn cell(&self, config: &MapConfig) -> IVec2 {
+        IVec2::new(
+            self.chunk.x * config.chunk_length as i32 + self.x as i32,
+            self.chunk.y * config.chunk_width as i32 + self.z as i32,
+        )
+    }
+
+    pub fn kind(&self) -> BlockKind {
+        self.kind
+    }
+
+    pub fn has_tower(&self) -> bool {
+        self.has_tower
+    }
+
+    /// Whether the block is the top of its column, the one towers are built on
+    pub fn is_top(&self, config: &MapConfig, surface: &Surface) -> bool {
+        matches!(surface.get(self.cell(config)), Some(c) if c.height == self.y + 1)
+    }
+
+    /// Whether a tower could stand on the block, leaving aside its cost and the enemy path
+    pub fn is_free(&self, config: &MapConfig, surface: &Surface) -> bool {
+        !self.has_tower && self.kind.is_buildable() && self.is_top(config, surface)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum BlockKind {
     Dirt,
     Stone,
+    Sand,
+    Water,
+}
+
+impl BlockKind {
+    pub const ALL: [BlockKind; 4] = [
+        BlockKind::Dirt,
+        BlockKind::Stone,
+        BlockKind::Sand,
+        BlockKind::Water,
+    ];
+
+    pub fn color(&self) -> Color {
+        match self {
+            BlockKind::Dirt => Color::rgb(0.45, 0.34, 0.22),
+            BlockKind::Stone => Color::rgb(0.45, 0.45, 0.48),
+            BlockKind::Sand => Color::rgb(0.86, 0.8, 0.55),
+            BlockKind::Water => Color::rgb(0.2, 0.4, 0.8),
+        }
+    }
+
+    /// Whether towers can be placed on top of this kind of block
+    pub fn is_buildable(&self) -> bool {
+        !matches!(self, BlockKind::Water)
+    }
+}
+
+#[derive(SystemLabel, Clone, Hash, Debug, PartialEq, Eq)]
+pub enum MapSystem {
+    Setup,
+    SyncSurface,
+}
+
+/// Dimensions shared by every chunk of the map
+#[derive(Clone, Debug)]
+pub struct MapConfig {
+    pub chunk_length: usize,
+    pub chunk_width: usize,
+    pub chunk_height: usize,
+    pub block_size: f32,
+    /// Spawn and goal cells of the enemy path, picked at random when `None`
+    pub path_endpoints: Option<(IVec2, IVec2)>,
+}
+
+impl MapConfig {
+    /// World cell of the column under `position`, see [`Surface`]
+    pub fn cell_at(&self, position: Vec3) -> IVec2 {
+        IVec2::new(
+            (position.x / self.block_size).round() as i32,
+            (position.z / self.block_size).round() as i32,
+        )
+    }
+}
+
+impl Default for MapConfig {
+    fn default() -> Self {
+        Self {
+            chunk_length: 10,
+            chunk_width: 10,
+            chunk_height: 6,
+            block_size: 5.0,
+            path_endpoints: None,
+        }
+    }
+}
+
+/// Gold the second chunk of the map costs, see [`ChunkMap::expansion_cost`]
+pub const EXPANSION_COST: u32 = 100;
+
+/// Every spawned chunk, indexed by its [`Chunk::coord`]
+#[derive(Default)]
+pub struct ChunkMap {
+    pub chunks: HashMap<IVec2, Entity>,
+}
+
+impl ChunkMap {
+    pub fn get(&self, coord: IVec2) -> Option<Entity> {
+        self.chunks.get(&coord).copied()
+    }
+
+    /// A chunk can be spawned on any free coordinate next to an existing chunk
+    pub fn can_expand_to(&self, coord: IVec2) -> bool {
+        !self.chunks.contains_key(&coord)
+            && neighbours(coord)
+                .iter()
+                .any(|neighbour| self.chunks.contains_key(neighbour))
+    }
+
+    /// Every chunk, ordered so that each one is next to a chunk listed before it. Expanding the
+    /// map to them in this order, from the first chunk, spawns the same map again.
+    pub fn expansion_order(&self) -> Vec<IVec2> {
+        let mut order = vec![IVec2::ZERO];
+        let mut next = 0;
+        while next < order.len() {
+            let mut found: Vec<IVec2> = neighbours(order[next])
+                .into_iter()
+                .filter(|coord| self.chunks.contains_key(coord) && !order.contains(coord))
+                .collect();
+            found.sort_by_key(|c| (c.x, c.y));
+            order.extend(found);
+            next += 1;
+        }
+        order
+    }
+
+    /// No chunk is spawned while there is no game, or when the map of its seed could not be set
+    /// up. Known right away, unlike a [`MapError`] which is inserted at the end of the frame.
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    /// Gold the next chunk costs, every chunk makes the next one dearer
+    pub fn expansion_cost(&self) -> u32 {
+        EXPANSION_COST * self.chunks.len() as u32
+    }
+
+    /// Free coordinate closest to the first chunk, the one the player buys next
+    pub fn next_expansion(&self) -> Option<IVec2> {
+        self.frontier()
+            .into_iter()
+            .min_by_key(|coord| coord.x.abs() + coord.y.abs())
+    }
+
+    /// Free coordinates the map can currently be expanded to
+    pub fn frontier(&self) -> Vec<IVec2> {
+        let mut frontier: Vec<IVec2> = self
+            .chunks
+            .keys()
+            .flat_map(|coord| neighbours(*coord))
+            .filter(|coord| !self.chunks.contains_key(coord))
+            .collect();
+        frontier.sort_by_key(|c| (c.x, c.y));
+        frontier.dedup();
+        frontier
+    }
+}
+
+fn neighbours(coord: IVec2) -> [IVec2; 4] {
+    [
+        coord + IVec2::new(1, 0),
+        coord + IVec2::new(-1, 0),
+        coord + IVec2::new(0, 1),
+        coord + IVec2::new(0, -1),
+    ]
+}
+
+/// Request to spawn a new chunk next to the existing ones
+pub struct ExpandMap {
+    pub coord: IVec2,
+}
+
+/// Left by a game that could not start because the map of its seed has no enemy path, for the
+/// start menu to tell the player
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MapError {
+    pub seed: u64,
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The map of seed {} has no path for the enemies, pick another seed",
+            self.seed
+        )
+    }
+}
+
+/// Seed fed into the [`MapGenerator`]. The same seed always produces the same map.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MapSeed(pub u64);
+
+impl MapSeed {
+    pub fn random() -> Self {
+        Self(rand::random())
+    }
+
+    /// The seed tried when the map of this one has no enemy path
+    pub fn next(&self) -> Self {
+        Self(self.0.wrapping_add(1))
+    }
+
+    pub fn rng(&self) -> StdRng {
+        StdRng::seed_from_u64(self.0)
+    }
+}
+
+impl Default for MapSeed {
+    fn default() -> Self {
+        Self::random()
+    }
+}
+
+/// The last [`MapSeed`] rolled at random rather than picked by the player. While the map is played
+/// on it, a map without an enemy path is replaced by the one of the next seed.
+pub struct RolledSeed(pub u64);
+
+/// Replaces the seed with a random one, which is rerolled if its map can not be played
+pub fn roll_seed(commands: &mut Commands, seed: &mut MapSeed) {
+    *seed = MapSeed::random();
+    commands.insert_resource(RolledSeed(seed.0));
+}
+
+/// Seeds tried after a [`RolledSeed`] before giving up with a [`MapError`]
+const MAX_REROLLS: u32 = 100;
+
+/// Block layout of a single chunk, produced by a [`MapGenerator`] before any entity is spawned.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChunkLayout {
+    pub length: usize,
+    pub width: usize,
+    pub height: usize,
+    /// One stack of blocks per (l, w) column, from the bottom up
+    columns: Vec<Vec<BlockKind>>,
 }
 
+impl ChunkLayout {
+    pub fn new(length: usize, width: usize, height: usize) -> Self {
+        Self {
+            length,
+            width,
+            height,
+            columns: vec![Vec::new(); length * width],
+        }
+    }
+
+    pub fn column(&self, l: usize, w: usize) -> &[BlockKind] {
+        &self.columns[l * self.width + w]
+    }
+
+    pub fn set_column(&mut self, l: usize, w: usize, column: Vec<BlockKind>) {
+        debug_assert!(column.len() <= self.height);
+        self.columns[l * self.width + w] = column;
+    }
+}
+
+/// Turns a seeded RNG into a [`ChunkLayout`]. Implementations must only draw randomness from
+/// `rng`, so that a given [`MapSeed`] always yields the same layout.
+///
+/// Every chunk of a map receives an identically seeded `rng`, `coord` tells them apart.
+pub trait MapGenerator: Send + Sync + 'static {
+    fn generate(
+        &self,
+        rng: &mut StdRng,
+        coord: IVec2,
+        length: usize,
+        width: usize,
+        height: usize,
+    ) -> ChunkLayout;
+}
+
+/// The [`MapGenerator`] used by the [`MapPlugin`]. Insert it before adding the plugin to override
+/// the default one.
+pub struct ActiveMapGenerator(pub Box<dyn MapGenerator>);
+
+/// Columns of uniformly random height, all made of stone.
+pub struct UniformGenerator;
+
+impl MapGenerator for UniformGenerator {
+    fn generate(
+        &self,
+        rng: &mut StdRng,
+        coord: IVec2,
+        length: usize,
+        width: usize,
+        height: usize,
+    ) -> ChunkLayout {
+        let chunk_seed = rng.gen::<u64>() ^ (((coord.x as u64) << 32) | coord.y as u32 as u64);
+        let rng = &mut StdRng::seed_from_u64(chunk_seed);
+        let mut layout = ChunkLayout::new(length, width, height);
+        for l in 0..length {
+            for w in 0..width {
+                let column_height = rng.gen_range(1..height);
+                layout.set_column(l, w, vec![BlockKind::Stone; column_height]);
+            }
+        }
+        layout
+    }
+}
+
+/// Mirrors [`Block::has_tower`] into the [`Surface`] so that pathfinding walks around towers
+fn sync_blocked_cells(
+    config: Res<MapConfig>,
+    mut surface: ResMut<Surface>,
+    query: Query<&Block, Changed<Block>>,
+) {
+    for block in query.iter() {
+        let cell = block.cell(&config);
+        if block.is_top(&config, &surface)
+            && surface.get(cell).map(|c| c.blocked) != Some(block.has_tower)
+        {
+            surface.set_blocked(cell, block.has_tower);
+        }
+    }
+}
+
+/// Makes the blocks under sold towers buildable again
+fn free_sold_blocks(mut events: EventReader<TowerSold>, mut query: Query<&mut Block>) {
+    for TowerSold { tower, .. } in events.iter() {
+        let found = query.iter_mut().find(|block| block.tower == Some(*tower));
+        if let Some(mut block) = found {
+            block.has_tower = false;
+            block.tower = None;
+        }
+    }
+}
+
+fn repath(surface: Res<Surface>, rules: Res<PathRules>, path: Option<ResMut<EnemyPath>>) {
+    if let Some(mut path) = path {
+        if (surface.is_changed() || rules.is_changed()) && !path.repath(&surface, &rules) {
+            warn!("No route left from {} to {}", path.spawn, path.goal);
+        }
+    }
+}
+
+/// Spawns a tower of the given kind on the block at `position`, `None` if the tower catalogue is
+/// not loaded yet
 fn spawn_tower_on_block(
     commands: &mut Commands,
+    kind: TowerKind,
     position: Vec3,
-    tower_assets: &ResMut<TowerAssets>,
-) {
-    super::tower::spawn_tower(commands, position, tower_assets);
+    tower_assets: &TowerAssets,
+    catalogues: &Assets<TowerCatalogue>,
+) -> Option<Entity> {
+    let spec = tower_assets.spec(catalogues, kind)?;
+    Some(super::tower::spawn_tower(
+        commands,
+        Tower::from_spec(kind, spec),
+        position,
+        spec,
+    ))
 }
 
-pub fn pick_block(
+/// Marks a block as the one a tower stands on
+fn occupy_block(block: &mut Block, tower: Entity) {
+    block.has_tower = true;
+    block.tower = Some(tower);
+}
+
+/// Spawns a tower from a saved game on the top block of `cell`. Nothing is paid for it and the
+/// placement is not checked again. `None` if there is no free block to build on, or if the tower
+/// catalogue is not loaded yet.
+pub fn restore_tower(
+    commands: &mut Commands,
+    tower: Tower,
+    cell: IVec2,
+    config: &MapConfig,
+    tower_assets: &TowerAssets,
+    catalogues: &Assets<TowerCatalogue>,
+    blocks: &mut Query<(&GlobalTransform, &mut Block)>,
+) -> Option<Entity> {
+    let (transform, mut block) = blocks
+        .iter_mut()
+        .filter(|(_, block)| block.cell(config) == cell)
+        .max_by_key(|(_, block)| block.y)?;
+    if block.has_tower || !block.kind.is_buildable() {
+        return None;
+    }
+    let spec = tower_assets.spec(catalogues, tower.kind)?;
+    let tower = super::tower::spawn_tower(commands, tower, transform.translation, spec);
+    occupy_block(&mut block, tower);
+    Some(tower)
+}
+
+/// Builds the towers of [`PlayerCommand::Build`]s, if they can be built
+#[allow(clippy::too_many_arguments)]
+fn build_towers(
     mut commands: Commands,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    tower_assets: ResMut<TowerAssets>,
-    mut events: EventReader<PickingEvent>,
-    mut query: Query<(
-        &mut Transform,
-        &mut Block,
-        &mut Handle<StandardMaterial>,
-        &PickableButton<StandardMaterial>,
-    )>,
+    player_commands: Res<PlayerCommands>,
+    tower_assets: Res<TowerAssets>,
+    catalogues: Res<Assets<TowerCatalogue>>,
+    mut resources: ResMut<PlayerResources>,
+    mut preview: ResMut<PlacementPreview>,
+    config: Res<MapConfig>,
+    surface: Res<Surface>,
+    rules: Res<PathRules>,
+    path: Option<Res<EnemyPath>>,
+    mut blocks: Query<(Entity, &GlobalTransform, &mut Block)>,
 ) {
-    for event in events.iter() {
-        if let PickingEvent::Clicked(e) = event {
-            if let Ok((transform, mut block, mut material, button)) = query.get_mut(*e) {
-                if !block.has_tower {
-                    //selection.set_selected(false);
-
-                    // TODO: Fix once every block has its own texture
-                    *material = button.initial.clone().unwrap();
-                    *material = materials.add(Color::rgb(0.0, 0.0, 1.0).into());
-                    commands.entity(*e).remove_bundle::<PickableBundle>();
-                    spawn_tower_on_block(&mut commands, transform.translation, &tower_assets);
-                    block.has_tower = true;
-                }
+    for command in player_commands.iter() {
+        let (kind, cell) = match command {
+            PlayerCommand::Build { kind, cell } => (*kind, IVec2::new(cell.0, cell.1)),
+            _ => continue,
+        };
+        let top = blocks
+            .iter_mut()
+            .filter(|(_, _, block)| block.cell(&config) == cell)
+            .max_by_key(|(_, _, block)| block.y);
+        let (entity, transform, mut block) = match top {
+            Some(top) => top,
+            None => continue,
+        };
+        let cost = match check_placement(
+            &block,
+            kind,
+            &config,
+            &surface,
+            &rules,
+            path.as_deref(),
+            &tower_assets,
+            &catalogues,
+            &resources,
+        ) {
+            Ok(cost) => cost,
+            Err(error) => {
+                info!("Can not build a {:?} tower on {}: {}", kind, cell, error);
+                continue;
             }
+        };
+        let tower = match spawn_tower_on_block(
+            &mut commands,
+            kind,
+            transform.translation,
+            &tower_assets,
+            &catalogues,
+        ) {
+            Some(tower) => tower,
+            None => continue,
+        };
+        resources.spend(cost);
+
+        occupy_block(&mut block, tower);
+        if preview.hovered == Some(entity) {
+            preview.hovered = None;
         }
     }
 }
+
+/// Sent when the player clicks on a block
+pub struct BlockClicked {
+    pub block: Entity,
+}
+
+/// The first click on a block previews the tower, a second one on the same block asks for it to
+/// be built. Clicks on blocks no tower could stand on are ignored.
+pub fn pick_block(
+    selection: Res<BuildSelection>,
+    config: Res<MapConfig>,
+    surface: Res<Surface>,
+    mut preview: ResMut<PlacementPreview>,
+    mut commands: ResMut<PlayerCommands>,
+    mut events: EventReader<BlockClicked>,
+    query: Query<&Block>,
+) {
+    for BlockClicked { block: e } in events.iter() {
+        let block = match query.get(*e) {
+            Ok(block) if block.is_free(&config, &surface) => block,
+            _ => continue,
+        };
+        if preview.pending != Some(*e) {
+            preview.pending = Some(*e);
+            continue;
+        }
+        preview.pending = None;
+
+        let cell = block.cell(&config);
+        commands.push(PlayerCommand::Build {
+            kind: selection.kind,
+            cell: (cell.x, cell.y),
+        });
+    }
+}
@@ -0,0 +1,177 @@
+use super::{find_path, find_path_avoiding, BlockKind, ChunkLayout, PathRules};
+use bevy::prelude::*;
+use rand::{rngs::StdRng, seq::SliceRandom};
+use std::collections::HashMap;
+
+/// Top block of every column of the map, indexed by world cell (x, z) across all chunks
+#[derive(Default)]
+pub struct Surface {
+    columns: HashMap<IVec2, Column>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Column {
+    /// Number of blocks in the column, the walkable top is the block at `height - 1`
+    pub height: usize,
+    pub kind: BlockKind,
+    /// A tower stands on top of the column
+    pub blocked: bool,
+}
+
+impl Surface {
+    pub fn insert_chunk(&mut self, coord: IVec2, layout: &ChunkLayout) {
+        for l in 0..layout.length {
+            for w in 0..layout.width {
+                let column = layout.column(l, w);
+                if let Some(kind) = column.last() {
+                    let cell = IVec2::new(
+                        coord.x * layout.length as i32 + l as i32,
+                        coord.y * layout.width as i32 + w as i32,
+                    );
+                    self.columns.insert(
+                        cell,
+                        Column {
+                            height: column.len(),
+                            kind: *kind,
+                            blocked: false,
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    pub fn get(&self, cell: IVec2) -> Option<&Column> {
+        self.columns.get(&cell)
+    }
+
+    pub fn is_walkable(&self, cell: IVec2, rules: &PathRules) -> bool {
+        matches!(self.get(cell), Some(c) if !c.blocked && rules.cost(c.kind).is_some())
+    }
+
+    pub fn set_blocked(&mut self, cell: IVec2, blocked: bool) {
+        if let Some(column) = self.columns.get_mut(&cell) {
+            column.blocked = blocked;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.columns.clear();
+    }
+
+    /// World position of the center of the top face of a column
+    pub fn world_position(&self, cell: IVec2, block_size: f32) -> Option<Vec3> {
+        self.get(cell).map(|c| {
+            Vec3::new(
+                cell.x as f32 * block_size,
+                (c.height as f32 - 0.5) * block_size,
+                cell.y as f32 * block_size,
+            )
+        })
+    }
+
+    /// Existing columns next to `cell`, whether they can be walked onto or not
+    pub fn adjacent(&self, cell: IVec2) -> impl Iterator<Item = IVec2> + '_ {
+        [
+            IVec2::new(1, 0),
+            IVec2::new(-1, 0),
+            IVec2::new(0, 1),
+            IVec2::new(0, -1),
+        ]
+        .into_iter()
+        .map(move |offset| cell + offset)
+        .filter(move |next| self.columns.contains_key(next))
+    }
+}
+
+/// Route enemies follow over the top of the blocks, from `spawn` to `goal` (both included)
+#[derive(Component, Clone, Debug)]
+pub struct EnemyPath {
+    pub spawn: IVec2,
+    pub goal: IVec2,
+    pub cells: Vec<IVec2>,
+}
+
+impl EnemyPath {
+    /// Recomputes the route between the same endpoints, `false` if they are no longer connected
+    pub fn repath(&mut self, surface: &Surface, rules: &PathRules) -> bool {
+        match find_path(surface, rules, self.spawn, self.goal) {
+            Some(cells) => {
+                self.cells = cells;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Whether a tower can be placed on `cell` while keeping the spawn connected to the goal
+    pub fn can_block(&self, surface: &Surface, rules: &PathRules, cell: IVec2) -> bool {
+        if cell == self.spawn || cell == self.goal {
+            return false;
+        }
+        !self.cells.contains(&cell)
+            || find_path_avoiding(surface, rules, self.spawn, self.goal, &[cell]).is_some()
+    }
+
+    pub fn waypoints(&self, surface: &Surface, block_size: f32) -> Vec<Vec3> {
+        self.cells
+            .iter()
+            .filter_map(|cell| surface.world_position(*cell, block_size))
+            .collect()
+    }
+}
+
+/// Picks a spawn and a goal on opposite edges of the chunk at `coord`, and connects them. Every
+/// pair across its rows is tried in a random order, then every pair across its columns. `None`
+/// means that no pair is connected, the chunk leaves the enemies no way through.
+pub fn generate_path(
+    surface: &Surface,
+    rules: &PathRules,
+    coord: IVec2,
+    length: usize,
+    width: usize,
+    rng: &mut StdRng,
+) -> Option<EnemyPath> {
+    let origin = IVec2::new(coord.x * length as i32, coord.y * width as i32);
+    let walkable = |cells: Vec<IVec2>| -> Vec<IVec2> {
+        cells
+            .into_iter()
+            .filter(|cell| surface.is_walkable(*cell, rules))
+            .collect()
+    };
+    let row = |l: usize| {
+        walkable(
+            (0..width)
+                .map(|w| origin + IVec2::new(l as i32, w as i32))
+                .collect(),
+        )
+    };
+    let column = |w: usize| {
+        walkable(
+            (0..length)
+                .map(|l| origin + IVec2::new(l as i32, w as i32))
+                .collect(),
+        )
+    };
+
+    [(row(0), row(length - 1)), (column(0), column(width - 1))]
+        .into_iter()
+        .find_map(|(mut spawns, mut goals)| {
+            spawns.shuffle(rng);
+            goals.shuffle(rng);
+            spawns
+                .iter()
+                .flat_map(|spawn| goals.iter().map(move |goal| (*spawn, *goal)))
+                .find_map(|(spawn, goal)| validate_path(surface, rules, spawn, goal))
+        })
+}
+
+/// Connects a given pair of endpoints, `None` if they are not connected
+pub fn validate_path(
+    surface: &Surface,
+    rules: &PathRules,
+    spawn: IVec2,
+    goal: IVec2,
+) -> Option<EnemyPath> {
+    find_path(surface, rules, spawn, goal).map(|cells| EnemyPath { spawn, goal, cells })
+}
@@ -0,0 +1,136 @@
+use super::{BlockKind, Column, Surface};
+use bevy::prelude::*;
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+};
+
+/// Constraints and costs of moving between neighbouring columns
+#[derive(Clone, Debug)]
+pub struct PathRules {
+    /// Maximum number of blocks an enemy can climb in a single step
+    pub max_climb: usize,
+    /// Maximum number of blocks an enemy can drop in a single step
+    pub max_drop: usize,
+    /// Extra cost per block climbed
+    pub climb_cost: u32,
+    /// Cost of walking onto each kind of block. Kinds without a cost are impassable.
+    pub costs: HashMap<BlockKind, u32>,
+}
+
+impl Default for PathRules {
+    fn default() -> Self {
+        Self {
+            max_climb: 1,
+            max_drop: 2,
+            climb_cost: 2,
+            costs: HashMap::from([
+                (BlockKind::Dirt, 2),
+                (BlockKind::Stone, 3),
+                (BlockKind::Sand, 4),
+            ]),
+        }
+    }
+}
+
+impl PathRules {
+    pub fn cost(&self, kind: BlockKind) -> Option<u32> {
+        self.costs.get(&kind).copied()
+    }
+
+    /// Cost of stepping from one column onto its neighbour, `None` if it is not possible
+    pub fn step_cost(&self, from: &Column, to: &Column) -> Option<u32> {
+        if to.blocked {
+            return None;
+        }
+        let cost = self.cost(to.kind)?;
+        if to.height > from.height {
+            let climb = to.height - from.height;
+            (climb <= self.max_climb).then_some(cost + climb as u32 * self.climb_cost)
+        } else {
+            (from.height - to.height <= self.max_drop).then_some(cost)
+        }
+    }
+
+    fn min_cost(&self) -> u32 {
+        self.costs.values().copied().min().unwrap_or(1)
+    }
+}
+
+/// Cheapest route between two cells, both included
+pub fn find_path(
+    surface: &Surface,
+    rules: &PathRules,
+    start: IVec2,
+    goal: IVec2,
+) -> Option<Vec<IVec2>> {
+    find_path_avoiding(surface, rules, start, goal, &[])
+}
+
+/// Same as [`find_path`], with `avoid` treated as blocked. Used to check whether blocking some
+/// cells would cut every route.
+pub fn find_path_avoiding(
+    surface: &Surface,
+    rules: &PathRules,
+    start: IVec2,
+    goal: IVec2,
+    avoid: &[IVec2],
+) -> Option<Vec<IVec2>> {
+    let passable = |cell: IVec2| surface.is_walkable(cell, rules) && !avoid.contains(&cell);
+    if !passable(start) || !passable(goal) {
+        return None;
+    }
+
+    // A* with a manhattan heuristic, admissible since every step costs at least `min_cost`
+    let min_cost = rules.min_cost();
+    let heuristic = |cell: IVec2| {
+        let d = (goal - cell).abs();
+        (d.x + d.y) as u32 * min_cost
+    };
+
+    let mut open = BinaryHeap::new();
+    let mut came_from: HashMap<IVec2, IVec2> = HashMap::new();
+    let mut cost_so_far: HashMap<IVec2, u32> = HashMap::new();
+    // Ties are broken on the cell so that the result never depends on the heap internals
+    open.push(Reverse((heuristic(start), 0, (start.x, start.y))));
+    cost_so_far.insert(start, 0);
+
+    while let Some(Reverse((_, cost, (x, z)))) = open.pop() {
+        let cell = IVec2::new(x, z);
+        if cell == goal {
+            let mut path = vec![goal];
+            let mut current = goal;
+            while current != start {
+                current = came_from[&current];
+                path.push(current);
+            }
+            path.reverse();
+            return Some(path);
+        }
+        if cost > cost_so_far[&cell] {
+            continue;
+        }
+
+        let from = surface.get(cell)?;
+        for next in surface.adjacent(cell) {
+            if !passable(next) {
+                continue;
+            }
+            let step = match rules.step_cost(from, surface.get(next)?) {
+                Some(step) => step,
+                None => continue,
+            };
+            let next_cost = cost + step;
+            if cost_so_far.get(&next).map_or(true, |&c| next_cost < c) {
+                cost_so_far.insert(next, next_cost);
+                came_from.insert(next, cell);
+                open.push(Reverse((
+                    next_cost + heuristic(next),
+                    next_cost,
+                    (next.x, next.y),
+                )));
+            }
+        }
+    }
+    None
+}
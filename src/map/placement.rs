@@ -0,0 +1,93 @@
+use super::{Block, EnemyPath, MapConfig, PathRules, Surface};
+use crate::{
+    economy::PlayerResources,
+    tower::{TowerAssets, TowerCatalogue, TowerKind},
+};
+use bevy::prelude::*;
+use std::fmt;
+
+/// Where the preview of the next tower is shown
+#[derive(Default)]
+pub struct PlacementPreview {
+    /// Block under the cursor
+    pub hovered: Option<Entity>,
+    /// Block clicked once, clicking it again builds the tower
+    pub pending: Option<Entity>,
+}
+
+impl PlacementPreview {
+    /// The pending block, or the hovered one when nothing is pending
+    pub fn target(&self) -> Option<Entity> {
+        self.pending.or(self.hovered)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlacementError {
+    Occupied,
+    /// The tower catalogue is not loaded yet
+    NotLoaded,
+    NotEnoughGold {
+        cost: u32,
+    },
+    /// Every route from spawn to goal would go through the block
+    BlocksPath,
+}
+
+impl fmt::Display for PlacementError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlacementError::Occupied => write!(f, "there already is a tower there"),
+            PlacementError::NotLoaded => write!(f, "the tower catalogue is not loaded yet"),
+            PlacementError::NotEnoughGold { cost } => write!(f, "{} gold needed", cost),
+            PlacementError::BlocksPath => write!(f, "it would block every route to the goal"),
+        }
+    }
+}
+
+/// Checks whether a tower of the given kind can be built on a block, and returns its cost if so
+#[allow(clippy::too_many_arguments)]
+pub fn check_placement(
+    block: &Block,
+    kind: TowerKind,
+    config: &MapConfig,
+    surface: &Surface,
+    rules: &PathRules,
+    path: Option<&EnemyPath>,
+    tower_assets: &TowerAssets,
+    catalogues: &Assets<TowerCatalogue>,
+    resources: &PlayerResources,
+) -> Result<u32, PlacementError> {
+    if block.has_tower {
+        return Err(PlacementError::Occupied);
+    }
+    let cost = match tower_assets.spec(catalogues, kind) {
+        Some(spec) => spec.cost,
+        None => return Err(PlacementError::NotLoaded),
+    };
+    if !resources.can_afford(cost) {
+        return Err(PlacementError::NotEnoughGold { cost });
+    }
+    if let Some(path) = path {
+        if !path.can_block(surface, rules, block.cell(config)) {
+            return Err(PlacementError::BlocksPath);
+        }
+    }
+    Ok(cost)
+}
+
+pub(super) fn cancel_placement(
+    keys: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    mut preview: ResMut<PlacementPreview>,
+) {
+    if preview.pending.is_some()
+        && (keys.just_pressed(KeyCode::Escape) || mouse.just_pressed(MouseButton::Right))
+    {
+        preview.pending = None;
+    }
+}
+
+pub(super) fn reset_preview(mut preview: ResMut<PlacementPreview>) {
+    *preview = PlacementPreview::default();
+}
@@ -0,0 +1,183 @@
+use super::{BlockKind, ChunkLayout, MapGenerator};
+use bevy::math::IVec2;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng};
+
+/// Layered value noise heightmap with plateaus, valleys and water.
+///
+/// Columns are built from the bottom up as stone, then a few layers of dirt. Columns that end
+/// below `water_level` get a sand floor and are flooded up to it, and columns right at the water
+/// line become sand beaches.
+pub struct TerrainGenerator {
+    /// Size of the biggest features, in blocks
+    pub scale: f32,
+    pub octaves: u32,
+    /// Amplitude falloff between octaves
+    pub persistence: f32,
+    /// Stretches the noise away from its mean, summed octaves rarely reach 0 or 1 otherwise
+    pub contrast: f32,
+    /// How strongly heights snap to flat plateaus, from 0 (none) to 1 (hard steps)
+    pub plateau_strength: f32,
+    /// Fraction of the valley noise range that gets carved down
+    pub valley_threshold: f32,
+    pub water_level: usize,
+    pub dirt_depth: usize,
+}
+
+impl Default for TerrainGenerator {
+    fn default() -> Self {
+        Self {
+            scale: 6.0,
+            octaves: 4,
+            persistence: 0.5,
+            contrast: 2.0,
+            plateau_strength: 0.7,
+            valley_threshold: 0.3,
+            water_level: 2,
+            dirt_depth: 1,
+        }
+    }
+}
+
+impl TerrainGenerator {
+    /// Ground height of a column, between 1 and `height - 1`
+    fn ground_height(
+        &self,
+        terrain: &ValueNoise,
+        valleys: &ValueNoise,
+        x: f32,
+        z: f32,
+        height: usize,
+    ) -> usize {
+        let mut h = terrain.fbm(
+            x / self.scale,
+            z / self.scale,
+            self.octaves,
+            self.persistence,
+        );
+        h = ((h - 0.5) * self.contrast + 0.5).clamp(0.0, 1.0);
+
+        // Plateaus: pull the height towards the closest step
+        let steps = (height - 1) as f32;
+        let stepped = (h * steps).round() / steps;
+        h += (stepped - h) * self.plateau_strength;
+
+        // Valleys: carve down wherever the low frequency noise is small
+        let v = valleys.fbm(x / (self.scale * 2.0), z / (self.scale * 2.0), 2, 0.5);
+        if v < self.valley_threshold {
+            h *= v / self.valley_threshold;
+        }
+
+        1 + (h * (steps - 1.0)).round() as usize
+    }
+
+    fn column(&self, ground: usize) -> Vec<BlockKind> {
+        let mut column: Vec<BlockKind> = (0..ground)
+            .map(|y| {
+                if y + self.dirt_depth >= ground {
+                    BlockKind::Dirt
+                } else {
+                    BlockKind::Stone
+                }
+            })
+            .collect();
+
+        // Beaches and the floor of lakes
+        if ground <= self.water_level {
+            if let Some(top) = column.last_mut() {
+                *top = BlockKind::Sand;
+            }
+        }
+        while column.len() < self.water_level {
+            column.push(BlockKind::Water);
+        }
+        column
+    }
+}
+
+impl MapGenerator for TerrainGenerator {
+    fn generate(
+        &self,
+        rng: &mut StdRng,
+        coord: IVec2,
+        length: usize,
+        width: usize,
+        height: usize,
+    ) -> ChunkLayout {
+        // Same tables for every chunk, sampled at world coordinates so that borders line up
+        let terrain = ValueNoise::new(rng);
+        let valleys = ValueNoise::new(rng);
+        let origin_x = coord.x * length as i32;
+        let origin_z = coord.y * width as i32;
+
+        let mut layout = ChunkLayout::new(length, width, height);
+        for l in 0..length {
+            for w in 0..width {
+                let (x, z) = ((origin_x + l as i32) as f32, (origin_z + w as i32) as f32);
+                let ground = self.ground_height(&terrain, &valleys, x, z, height);
+                layout.set_column(l, w, self.column(ground));
+            }
+        }
+        layout
+    }
+}
+
+/// 2D value noise over a shuffled lattice, in the range [0, 1]
+struct ValueNoise {
+    values: Vec<f32>,
+    permutation: Vec<usize>,
+}
+
+impl ValueNoise {
+    const SIZE: usize = 256;
+
+    fn new(rng: &mut StdRng) -> Self {
+        let values = (0..Self::SIZE).map(|_| rng.gen::<f32>()).collect();
+        let mut permutation: Vec<usize> = (0..Self::SIZE).collect();
+        permutation.shuffle(rng);
+        // Duplicated so that lookups never need to wrap twice
+        permutation.extend_from_within(..);
+        Self {
+            values,
+            permutation,
+        }
+    }
+
+    fn lattice(&self, x: i32, z: i32) -> f32 {
+        let mask = Self::SIZE as i32 - 1;
+        let i = self.permutation[(x & mask) as usize];
+        self.values[self.permutation[i + (z & mask) as usize]]
+    }
+
+    fn sample(&self, x: f32, z: f32) -> f32 {
+        let (x0, z0) = (x.floor(), z.floor());
+        let (tx, tz) = (smoothstep(x - x0), smoothstep(z - z0));
+        let (x0, z0) = (x0 as i32, z0 as i32);
+
+        let top = lerp(self.lattice(x0, z0), self.lattice(x0 + 1, z0), tx);
+        let bottom = lerp(self.lattice(x0, z0 + 1), self.lattice(x0 + 1, z0 + 1), tx);
+        lerp(top, bottom, tz)
+    }
+
+    /// Fractal sum of `octaves` layers, each with double the frequency of the previous one
+    fn fbm(&self, x: f32, z: f32, octaves: u32, persistence: f32) -> f32 {
+        let mut total = 0.0;
+        let mut amplitude = 1.0;
+        let mut max = 0.0;
+        let mut frequency = 1.0;
+        for _ in 0..octaves {
+            total += self.sample(x * frequency, z * frequency) * amplitude;
+            max += amplitude;
+            amplitude *= persistence;
+            frequency *= 2.0;
+        }
+        total / max
+    }
+}
+
+fn smoothstep(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
@@ -0,0 +1,310 @@
+use crate::{
+    game_state::{GameState, StateScoped},
+    map::PlacementPreview,
+    settings::Settings,
+    tower::SelectedTower,
+};
+use bevy::prelude::*;
+
+// https://github.com/Leafwing-Studios/leafwing-input-manager/blob/446ac84cfcd2c76ae5607cca1c871681af09a0d9/src/lib.rs#L98
+#[derive(Default)]
+pub struct PauseMenuPlugin {
+    desired_state: Option<GameState>,
+}
+
+impl PauseMenuPlugin {
+    pub fn new() -> Self {
+        Self {
+            desired_state: None,
+        }
+    }
+
+    pub fn run_in_state(state: GameState) -> Self {
+        Self {
+            desired_state: Some(state),
+        }
+    }
+}
+
+impl Plugin for PauseMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Settings>();
+        if let Some(desired_state) = self.desired_state {
+            // The pause menu is pushed on top of the game, which keeps its world while paused
+            app.add_system_set(
+                SystemSet::on_update(GameState::Defense)
+                    .with_system(pause_game.label(PauseSystem::Pause)),
+            )
+            .add_system_set(SystemSet::on_enter(desired_state).with_system(setup))
+            .add_system_set(
+                SystemSet::on_update(desired_state)
+                    .with_system(resume_game)
+                    .with_system(button_selection)
+                    .with_system(update_camera_speed_text),
+            );
+        } else {
+            panic!("PauseMenuPlugin::run_in_state() must be called with a GameState");
+        }
+    }
+}
+
+/// Systems that also react to Escape run after [`PauseSystem::Pause`], so that Escape first
+/// cancels what they are doing and only pauses the game once there is nothing left to cancel
+#[derive(SystemLabel, Clone, Hash, Debug, PartialEq, Eq)]
+pub enum PauseSystem {
+    Pause,
+}
+
+const TEXT_COLOR: Color = Color::rgb(0.8, 0.8, 0.8);
+const BUTTON_COLOR: Color = Color::rgb(0.1, 0.1, 0.1);
+const HOVERED_BUTTON_COLOR: Color = Color::rgb(0.2, 0.2, 0.2);
+const PRESSED_BUTTON_COLOR: Color = Color::rgb(0.3, 0.3, 0.3);
+
+/// Change of [`Settings::camera_speed`] per click
+const CAMERA_SPEED_STEP: f32 = 0.25;
+
+fn pause_game(
+    mut keys: ResMut<Input<KeyCode>>,
+    preview: Res<PlacementPreview>,
+    selected: Res<SelectedTower>,
+    mut game_state: ResMut<State<GameState>>,
+) {
+    let escape =
+        keys.just_pressed(KeyCode::Escape) && preview.pending.is_none() && selected.tower.is_none();
+    if !escape && !keys.just_pressed(KeyCode::P) {
+        return;
+    }
+    // The pause menu is updated in the same frame, it must not see the key as pressed again
+    keys.reset(KeyCode::Escape);
+    keys.reset(KeyCode::P);
+    if let Err(e) = game_state.push(GameState::Paused) {
+        warn!("Could not pause the game: {}", e);
+    }
+}
+
+fn resume_game(mut keys: ResMut<Input<KeyCode>>, mut game_state: ResMut<State<GameState>>) {
+    if !keys.just_pressed(KeyCode::Escape) && !keys.just_pressed(KeyCode::P) {
+        return;
+    }
+    keys.reset(KeyCode::Escape);
+    keys.reset(KeyCode::P);
+    if let Err(e) = game_state.pop() {
+        warn!("Could not resume the game: {}", e);
+    }
+}
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/FiraMono-Regular.ttf");
+    let text = |value: &str, font_size: f32| TextBundle {
+        style: Style {
+            margin: Rect::all(Val::Px(8.)),
+            ..Default::default()
+        },
+        text: Text::with_section(
+            value.to_string(),
+            TextStyle {
+                font: font.clone(),
+                font_size,
+                color: TEXT_COLOR,
+            },
+            TextAlignment {
+                vertical: VerticalAlign::Center,
+                horizontal: HorizontalAlign::Center,
+            },
+        ),
+        ..Default::default()
+    };
+    let button = |width: f32| ButtonBundle {
+        style: Style {
+            min_size: Size::new(Val::Px(width), Val::Px(60.)),
+            margin: Rect::all(Val::Px(8.)),
+            padding: Rect::all(Val::Px(8.)),
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            ..Default::default()
+        },
+        color: BUTTON_COLOR.into(),
+        ..Default::default()
+    };
+    let page = |page: PausePage| NodeBundle {
+        style: Style {
+            display: if page == PausePage::Main {
+                Display::Flex
+            } else {
+                Display::None
+            },
+            flex_direction: FlexDirection::ColumnReverse,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        color: Color::NONE.into(),
+        ..Default::default()
+    };
+
+    // Covers the whole screen, so that the hud below can not be clicked
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect::all(Val::Px(0.)),
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+            ..Default::default()
+        })
+        .insert(StateScoped(GameState::Paused))
+        .insert(Name::new("pause_menu"))
+        .with_children(|parent| {
+            parent.spawn_bundle(text("Paused", 64.0));
+
+            parent
+                .spawn_bundle(page(PausePage::Main))
+                .insert(PausePage::Main)
+                .with_children(|parent| {
+                    for (label, action) in [
+                        ("Resume", ButtonAction::Resume),
+                        ("Settings", ButtonAction::Show(PausePage::Settings)),
+                        ("Quit to menu", ButtonAction::Quit),
+                    ] {
+                        parent
+                            .spawn_bundle(button(300.))
+                            .insert(Name::new(format!("button:{}", label)))
+                            .insert(action)
+                            .with_children(|parent| {
+                                parent.spawn_bundle(text(label, 36.0));
+                            });
+                    }
+                });
+
+            parent
+                .spawn_bundle(page(PausePage::Settings))
+                .insert(PausePage::Settings)
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(NodeBundle {
+                            style: Style {
+                                align_items: AlignItems::Center,
+                                ..Default::default()
+                            },
+                            color: Color::NONE.into(),
+                            ..Default::default()
+                        })
+                        .with_children(|parent| {
+                            parent
+                                .spawn_bundle(button(60.))
+                                .insert(ButtonAction::CameraSpeed(-CAMERA_SPEED_STEP))
+                                .with_children(|parent| {
+                                    parent.spawn_bundle(text("-", 36.0));
+                                });
+                            parent
+                                .spawn_bundle(text("", 28.0))
+                                .insert(CameraSpeedText {});
+                            parent
+                                .spawn_bundle(button(60.))
+                                .insert(ButtonAction::CameraSpeed(CAMERA_SPEED_STEP))
+                                .with_children(|parent| {
+                                    parent.spawn_bundle(text("+", 36.0));
+                                });
+                        });
+                    parent
+                        .spawn_bundle(button(300.))
+                        .insert(Name::new("button:Back"))
+                        .insert(ButtonAction::Show(PausePage::Main))
+                        .with_children(|parent| {
+                            parent.spawn_bundle(text("Back", 36.0));
+                        });
+                });
+        });
+}
+
+#[allow(clippy::type_complexity)]
+fn button_selection(
+    mut game_state: ResMut<State<GameState>>,
+    mut settings: ResMut<Settings>,
+    mut pages: Query<(&PausePage, &mut Style)>,
+    mut interaction_query: Query<
+        (&Interaction, &mut UiColor, &ButtonAction),
+        (Changed<Interaction>, With<Button>),
+    >,
+) {
+    for (interaction, mut color, button_action) in interaction_query.iter_mut() {
+        match *interaction {
+            Interaction::Clicked => {
+                *color = PRESSED_BUTTON_COLOR.into();
+                button_action.run(&mut game_state, &mut settings, &mut pages);
+            }
+            Interaction::Hovered => {
+                *color = HOVERED_BUTTON_COLOR.into();
+            }
+            Interaction::None => {
+                *color = BUTTON_COLOR.into();
+            }
+        }
+    }
+}
+
+fn update_camera_speed_text(
+    settings: Res<Settings>,
+    mut query: Query<&mut Text, With<CameraSpeedText>>,
+) {
+    let value = format!("Camera speed: {:.2}x", settings.camera_speed);
+    for mut text in query.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+#[derive(Component)]
+struct CameraSpeedText {}
+
+/// Only one page of the pause menu is shown at a time
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+enum PausePage {
+    Main,
+    Settings,
+}
+
+#[derive(Component, Clone, Copy)]
+enum ButtonAction {
+    Resume,
+    Show(PausePage),
+    CameraSpeed(f32),
+    Quit,
+}
+
+impl ButtonAction {
+    fn run(
+        &self,
+        game_state: &mut State<GameState>,
+        settings: &mut Settings,
+        pages: &mut Query<(&PausePage, &mut Style)>,
+    ) {
+        let result = match self {
+            ButtonAction::Resume => game_state.pop(),
+            // Leaves the paused game too, tearing it down as if it had been left directly
+            ButtonAction::Quit => game_state.replace(GameState::StartMenu),
+            ButtonAction::Show(shown) => {
+                pages.for_each_mut(|(page, mut style)| {
+                    style.display = if page == shown {
+                        Display::Flex
+                    } else {
+                        Display::None
+                    };
+                });
+                Ok(())
+            }
+            ButtonAction::CameraSpeed(step) => {
+                settings.camera_speed = (settings.camera_speed + step)
+                    .clamp(Settings::MIN_CAMERA_SPEED, Settings::MAX_CAMERA_SPEED);
+                Ok(())
+            }
+        };
+        if let Err(e) = result {
+            warn!("Could not leave the pause menu: {}", e);
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use crate::{
+    enemy::{Enemy, PathFollower, Slowed},
+    game_state::{GameState, StateScoped},
+    sim::{SimSystem, SimTime, SimulationApp},
+    tower::{Tower, TowerCannon, TowerSystem},
+};
+use bevy::prelude::*;
+use serde::Deserialize;
+
+// https://github.com/Leafwing-Studios/leafwing-input-manager/blob/446ac84cfcd2c76ae5607cca1c871681af09a0d9/src/lib.rs#L98
+#[derive(Default)]
+pub struct ProjectilePlugin {
+    desired_state: Option<GameState>,
+}
+
+impl ProjectilePlugin {
+    pub fn new() -> Self {
+        Self {
+            desired_state: None,
+        }
+    }
+
+    pub fn run_in_state(state: GameState) -> Self {
+        Self {
+            desired_state: Some(state),
+        }
+    }
+}
+
+impl Plugin for ProjectilePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DamageDealt>().add_event::<EnemyKilled>();
+        if self.desired_state.is_some() {
+            app.add_sim_system_set(
+                SystemSet::new()
+                    .label(SimSystem::Combat)
+                    .after(SimSystem::Movement)
+                    .with_system(
+                        fire_towers
+                            .label(ProjectileSystem::Fire)
+                            .after(TowerSystem::Target),
+                    )
+                    .with_system(move_projectiles.after(ProjectileSystem::Fire)),
+            );
+        } else {
+            panic!("ProjectilePlugin::run_in_state() must be called with a GameState");
+        }
+    }
+}
+
+#[derive(SystemLabel, Clone, Hash, Debug, PartialEq, Eq)]
+pub enum ProjectileSystem {
+    Fire,
+}
+
+/// How a tower's projectiles fly, and what they do on impact
+#[derive(Deserialize, Clone, Debug)]
+pub struct ProjectileSpec {
+    pub kind: ProjectileKind,
+    /// World units per second
+    pub speed: f32,
+    /// Enemies within this distance of the impact are hit too, 0 for single target
+    #[serde(default)]
+    pub splash_radius: f32,
+    #[serde(default)]
+    pub slow: Option<SlowEffect>,
+}
+
+/// Slows down the enemies hit
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct SlowEffect {
+    /// Speed multiplier while slowed
+    pub factor: f32,
+    /// Seconds
+    pub duration: f32,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum ProjectileKind {
+    /// Lobbed at where the target stands when fired, and explodes on landing
+    Ballistic { arc_height: f32 },
+    /// Follows its target until it hits it
+    Homing,
+}
+
+/// Sent for every enemy hit, splash damage included
+pub struct DamageDealt {
+    pub tower: Entity,
+    pub enemy: Entity,
+    pub amount: f32,
+}
+
+/// Sent when a hit takes the last of an enemy's health, right before it is despawned
+pub struct EnemyKilled {
+    pub tower: Entity,
+    pub enemy: Entity,
+    pub bounty: u32,
+}
+
+#[derive(Component)]
+pub struct Projectile {
+    /// Tower that fired the projectile
+    pub tower: Entity,
+    pub damage: f32,
+    pub splash_radius: f32,
+    pub slow: Option<SlowEffect>,
+    pub motion: Motion,
+}
+
+#[derive(Clone, Debug)]
+pub enum Motion {
+    Ballistic {
+        start: Vec3,
+        end: Vec3,
+        arc_height: f32,
+        duration: f32,
+        elapsed: f32,
+    },
+    Homing {
+        target: Entity,
+        /// Where the target was last seen, the projectile keeps going there if it disappears
+        last_seen: Vec3,
+        speed: f32,
+    },
+}
+
+/// Distance from the cannon's origin to its muzzle, along its forward axis
+const MUZZLE_LENGTH: f32 = 2.0;
+
+/// Fires a projectile from every loaded tower that has a target
+fn fire_towers(
+    mut commands: Commands,
+    time: Res<SimTime>,
+    mut towers: Query<(Entity, &mut Tower, &Children)>,
+    cannons: Query<&GlobalTransform, With<TowerCannon>>,
+    targets: Query<(&GlobalTransform, &PathFollower), With<Enemy>>,
+) {
+    for (entity, mut tower, children) in towers.iter_mut() {
+        tower.reload = (tower.reload - time.delta_seconds()).max(0.0);
+        let target = match tower.target {
+            Some(target) => target,
+            None => continue,
+        };
+        if tower.reload > 0.0 {
+            continue;
+        }
+        let (target_position, cannon) = match (
+            targets.get(target),
+            children.iter().find_map(|c| cannons.get(*c).ok()),
+        ) {
+            (Ok((target, follower)), Some(cannon)) if !follower.reached_goal() => {
+                (target.translation, cannon)
+            }
+            _ => continue,
+        };
+
+        let muzzle = cannon.translation + cannon.rotation * Vec3::new(0.0, 0.0, -MUZZLE_LENGTH);
+        let spec = &tower.projectile;
+        let motion = match spec.kind {
+            ProjectileKind::Ballistic { arc_height } => Motion::Ballistic {
+                start: muzzle,
+                end: target_position,
+                arc_height,
+                duration: muzzle.distance(target_position) / spec.speed,
+                elapsed: 0.0,
+            },
+            ProjectileKind::Homing => Motion::Homing {
+                target,
+                last_seen: target_position,
+                speed: spec.speed,
+            },
+        };
+        commands
+            .spawn_bundle((
+                Transform::from_translation(muzzle),
+                GlobalTransform::default(),
+            ))
+            .insert(Projectile {
+                tower: entity,
+                damage: tower.damage,
+                splash_radius: spec.splash_radius,
+                slow: spec.slow,
+                motion,
+            })
+            .insert(StateScoped(GameState::Defense))
+            .insert(Name::new("projectile"));
+        tower.reload = 1.0 / tower.fire_rate;
+    }
+}
+
+/// Moves projectiles, and applies their damage once they land
+fn move_projectiles(
+    mut commands: Commands,
+    time: Res<SimTime>,
+    mut damage_events: EventWriter<DamageDealt>,
+    mut killed_events: EventWriter<EnemyKilled>,
+    mut projectiles: Query<(Entity, &mut Projectile, &mut Transform)>,
+    mut enemies: Query<(Entity, &GlobalTransform, &mut Enemy, &PathFollower)>,
+) {
+    let delta = time.delta_seconds();
+    for (entity, mut projectile, mut transform) in projectiles.iter_mut() {
+        // Where the projectile lands this frame, and the enemy it was aimed at for direct hits
+        let impact = match &mut projectile.motion {
+            Motion::Ballistic {
+                start,
+                end,
+                arc_height,
+                duration,
+                elapsed,
+            } => {
+                *elapsed += delta;
+                let t = if *duration > 0.0 {
+                    (*elapsed / *duration).min(1.0)
+                } else {
+                    1.0
+                };
+                transform.translation =
+                    start.lerp(*end, t) + Vec3::Y * (4.0 * *arc_height * t * (1.0 - t));
+                (t >= 1.0).then_some((*end, None))
+            }
+            Motion::Homing {
+                target,
+                last_seen,
+                speed,
+            } => {
+                if let Ok((_, target_transform, _, _)) = enemies.get(*target) {
+                    *last_seen = target_transform.translation;
+                }
+                let to_target = *last_seen - transform.translation;
+                let step = *speed * delta;
+                if to_target.length() <= step {
+                    transform.translation = *last_seen;
+                    Some((*last_seen, Some(*target)))
+                } else {
+                    transform.translation += to_target.normalize() * step;
+                    None
+                }
+            }
+        };
+
+        let (position, direct) = match impact {
+            Some(impact) => impact,
+            None => continue,
+        };
+        commands.entity(entity).despawn_recursive();
+
+        for (enemy_entity, enemy_transform, mut enemy, follower) in enemies.iter_mut() {
+            // Already killed by another projectile this frame, or leaving through the goal
+            if enemy.health <= 0.0 || follower.reached_goal() {
+                continue;
+            }
+            let distance = enemy_transform.translation.distance(position);
+            let hit = if projectile.splash_radius > 0.0 {
+                distance <= projectile.splash_radius + enemy.radius
+            } else {
+                direct == Some(enemy_entity) || (direct.is_none() && distance <= enemy.radius)
+            };
+            if !hit {
+                continue;
+            }
+
+            let amount = projectile.damage.min(enemy.health);
+            enemy.health -= amount;
+            damage_events.send(DamageDealt {
+                tower: projectile.tower,
+                enemy: enemy_entity,
+                amount,
+            });
+            if let Some(slow) = projectile.slow {
+                enemy.slowed = Some(Slowed {
+                    factor: slow.factor,
+                    remaining: slow.duration,
+                });
+            }
+            if enemy.health <= 0.0 {
+                killed_events.send(EnemyKilled {
+                    tower: projectile.tower,
+                    enemy: enemy_entity,
+                    bounty: enemy.bounty,
+                });
+                commands.entity(enemy_entity).despawn_recursive();
+            }
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use crate::{game_state::GameState, map::MapConfig};
+use bevy::prelude::*;
+use bevy_mod_picking::*;
+
+mod enemy;
+mod map;
+mod projectile;
+mod tower;
+pub use enemy::EnemyAssets;
+pub use map::BlockAssets;
+pub use projectile::ProjectileAssets;
+pub use tower::{TowerKindAssets, TowerRenderAssets};
+
+// https://github.com/Leafwing-Studios/leafwing-input-manager/blob/446ac84cfcd2c76ae5607cca1c871681af09a0d9/src/lib.rs#L98
+/// Meshes, materials and picking of everything the gameplay plugins spawn. They only spawn game
+/// data, this plugin gives it a look once it exists, so the game also runs without it.
+#[derive(Default)]
+pub struct RenderingPlugin {
+    desired_state: Option<GameState>,
+}
+
+impl RenderingPlugin {
+    pub fn new() -> Self {
+        Self {
+            desired_state: None,
+        }
+    }
+
+    pub fn run_in_state(state: GameState) -> Self {
+        Self {
+            desired_state: Some(state),
+        }
+    }
+}
+
+impl Plugin for RenderingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(DefaultPickingPlugins);
+        app.init_resource::<BlockAssets>()
+            .init_resource::<TowerRenderAssets>()
+            .init_resource::<EnemyAssets>()
+            .init_resource::<ProjectileAssets>()
+            .add_startup_system(load_assets)
+            .add_system(tower::load_kind_assets)
+            .add_system(map::sync_block_visuals)
+            .add_system(tower::add_tower_meshes)
+            .add_system(tower::show_upgrades)
+            .add_system(enemy::add_enemy_meshes)
+            .add_system(projectile::add_projectile_meshes);
+
+        if let Some(desired_state) = self.desired_state {
+            app.insert_resource(PickingPluginsState {
+                enable_picking: false,
+                ..Default::default()
+            });
+            app.add_system_set_to_stage(
+                CoreStage::PreUpdate,
+                SystemSet::new()
+                    .with_system(map::track_hovered_block)
+                    .with_system(map::forward_block_clicks),
+            );
+            app.add_system_set(SystemSet::on_update(desired_state).with_system(map::update_ghost));
+            app.add_system_set(SystemSet::on_enter(desired_state).with_system(enable_picking))
+                // Blocks and towers can not be clicked through the pause menu
+                .add_system_set(SystemSet::on_pause(desired_state).with_system(disable_picking))
+                .add_system_set(SystemSet::on_resume(desired_state).with_system(enable_picking))
+                .add_system_set(SystemSet::on_exit(desired_state).with_system(disable_picking));
+        } else {
+            panic!("RenderingPlugin::run_in_state() must be called with a GameState");
+        }
+    }
+}
+
+/// Creates the meshes and materials of the map, enemies and projectiles. Those of the towers are
+/// loaded along with the tower catalogue.
+#[allow(clippy::too_many_arguments)]
+fn load_assets(
+    config: Res<MapConfig>,
+    mut block_assets: ResMut<BlockAssets>,
+    mut tower_assets: ResMut<TowerRenderAssets>,
+    mut enemy_assets: ResMut<EnemyAssets>,
+    mut projectile_assets: ResMut<ProjectileAssets>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    block_assets.mesh = meshes.add(Mesh::from(shape::Cube {
+        size: config.block_size,
+    }));
+    block_assets.materials = crate::map::BlockKind::ALL
+        .iter()
+        .map(|kind| (*kind, materials.add(kind.color().into())))
+        .collect();
+    block_assets.occupied_material = materials.add(Color::rgb(0.0, 0.0, 1.0).into());
+    tower_assets.range_mesh = meshes.add(Mesh::from(shape::Torus {
+        radius: 1.0,
+        ring_radius: 0.01,
+        subdivisions_segments: 64,
+        subdivisions_sides: 4,
+    }));
+    tower_assets.range_material = materials.add(StandardMaterial {
+        base_color: Color::rgba(1.0, 1.0, 1.0, 0.6),
+        alpha_mode: AlphaMode::Blend,
+        unlit: true,
+        ..Default::default()
+    });
+    tower_assets.ghost_material = materials.add(StandardMaterial {
+        base_color: Color::rgba(0.6, 0.9, 1.0, 0.4),
+        alpha_mode: AlphaMode::Blend,
+        ..Default::default()
+    });
+    tower_assets.invalid_ghost_material = materials.add(StandardMaterial {
+        base_color: Color::rgba(1.0, 0.2, 0.2, 0.4),
+        alpha_mode: AlphaMode::Blend,
+        ..Default::default()
+    });
+    enemy_assets.mesh = meshes.add(Mesh::from(shape::Icosphere {
+        radius: 1.0,
+        subdivisions: 2,
+    }));
+    enemy_assets.material = materials.add(Color::rgb(0.7, 0.1, 0.1).into());
+    projectile_assets.mesh = meshes.add(Mesh::from(shape::Icosphere {
+        radius: 0.6,
+        subdivisions: 1,
+    }));
+    projectile_assets.material = materials.add(Color::rgb(0.1, 0.1, 0.1).into());
+}
+
+fn enable_picking(
+    mut state: ResMut<PickingPluginsState>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut picking_materials: ResMut<
+        MeshButtonMaterials<StandardMaterial, StandardMaterialPickingColors>,
+    >,
+) {
+    state.enable_picking = true;
+    picking_materials.pressed = materials.add(Color::rgba(1.0, 0.0, 0.0, 1.0).into());
+}
+
+fn disable_picking(mut state: ResMut<PickingPluginsState>) {
+    state.enable_picking = false;
+}
@@ -0,0 +1,23 @@
+use crate::enemy::Enemy;
+use bevy::prelude::*;
+
+#[derive(Default, Clone)]
+pub struct EnemyAssets {
+    pub mesh: Handle<Mesh>,
+    pub material: Handle<StandardMaterial>,
+}
+
+pub(super) fn add_enemy_meshes(
+    mut commands: Commands,
+    enemy_assets: Res<EnemyAssets>,
+    enemies: Query<Entity, (With<Enemy>, Without<Handle<Mesh>>)>,
+) {
+    enemies.for_each(|e| {
+        commands.entity(e).insert_bundle((
+            enemy_assets.mesh.clone(),
+            enemy_assets.material.clone(),
+            Visibility::default(),
+            ComputedVisibility::default(),
+        ));
+    });
+}
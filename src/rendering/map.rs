@@ -0,0 +1,195 @@
+use super::tower::{spawn_tower_ghost, TowerRenderAssets};
+use crate::{
+    economy::PlayerResources,
+    game_state::{GameState, StateScoped},
+    map::{
+        check_placement, Block, BlockClicked, BlockKind, EnemyPath, MapConfig, PathRules,
+        PlacementPreview, Surface,
+    },
+    tower::{BuildSelection, TowerAssets, TowerCatalogue, TowerKind},
+};
+use bevy::prelude::*;
+use bevy_mod_picking::{HoverEvent, PickableBundle, PickableButton, PickingEvent};
+use std::collections::HashMap;
+
+#[derive(Default, Clone)]
+pub struct BlockAssets {
+    pub mesh: Handle<Mesh>,
+    pub materials: HashMap<BlockKind, Handle<StandardMaterial>>,
+    /// Shown on the blocks towers stand on
+    pub occupied_material: Handle<StandardMaterial>,
+}
+
+impl BlockAssets {
+    pub fn material(&self, block: &Block) -> Handle<StandardMaterial> {
+        if block.has_tower() {
+            return self.occupied_material.clone();
+        }
+        self.materials
+            .get(&block.kind())
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// Gives new blocks their mesh, and every changed block the material and picking that match
+/// whether a tower can be built on it
+#[allow(clippy::type_complexity)]
+pub(super) fn sync_block_visuals(
+    mut commands: Commands,
+    block_assets: Res<BlockAssets>,
+    config: Res<MapConfig>,
+    surface: Res<Surface>,
+    blocks: Query<
+        (
+            Entity,
+            &Block,
+            Option<&Handle<Mesh>>,
+            Option<&PickableButton<StandardMaterial>>,
+        ),
+        Or<(Changed<Block>, Without<Handle<Mesh>>)>,
+    >,
+) {
+    for (entity, block, mesh, button) in blocks.iter() {
+        let mut block_commands = commands.entity(entity);
+        if mesh.is_none() {
+            block_commands.insert_bundle((
+                block_assets.mesh.clone(),
+                Visibility::default(),
+                ComputedVisibility::default(),
+            ));
+        }
+        block_commands.insert(block_assets.material(block));
+        let pickable = block.is_free(&config, &surface);
+        if pickable && button.is_none() {
+            block_commands.insert_bundle(PickableBundle::default());
+        } else if !pickable && button.is_some() {
+            // Picking stops tracking the block, so it would stay hovered forever
+            block_commands.remove_bundle::<PickableBundle>();
+        }
+    }
+}
+
+/// Tells the map about the blocks clicked on
+pub(super) fn forward_block_clicks(
+    mut events: EventReader<PickingEvent>,
+    mut clicked: EventWriter<BlockClicked>,
+    blocks: Query<(), With<Block>>,
+) {
+    for event in events.iter() {
+        if let PickingEvent::Clicked(e) = event {
+            if blocks.get(*e).is_ok() {
+                clicked.send(BlockClicked { block: *e });
+            }
+        }
+    }
+}
+
+pub(super) fn track_hovered_block(
+    mut events: EventReader<PickingEvent>,
+    mut preview: ResMut<PlacementPreview>,
+    blocks: Query<(), With<Block>>,
+) {
+    for event in events.iter() {
+        match event {
+            PickingEvent::Hover(HoverEvent::JustEntered(e)) if blocks.get(*e).is_ok() => {
+                preview.hovered = Some(*e);
+            }
+            PickingEvent::Hover(HoverEvent::JustLeft(e)) if preview.hovered == Some(*e) => {
+                preview.hovered = None;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Shows a see-through tower of the selected kind, and its range, on the previewed block. Both
+/// turn red when the tower can not be built there.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn update_ghost(
+    mut commands: Commands,
+    preview: Res<PlacementPreview>,
+    selection: Res<BuildSelection>,
+    resources: Res<PlayerResources>,
+    config: Res<MapConfig>,
+    surface: Res<Surface>,
+    rules: Res<PathRules>,
+    path: Option<Res<EnemyPath>>,
+    tower_assets: Res<TowerAssets>,
+    render_assets: Res<TowerRenderAssets>,
+    catalogues: Res<Assets<TowerCatalogue>>,
+    blocks: Query<(&Block, &GlobalTransform)>,
+    mut ghosts: Query<(Entity, &PlacementGhost, &mut Transform)>,
+) {
+    let target = preview.target().and_then(|e| {
+        let (block, transform) = blocks.get(e).ok()?;
+        let spec = tower_assets.spec(&catalogues, selection.kind)?;
+        let kind_assets = render_assets.kinds.get(&selection.kind)?;
+        Some((block, transform.translation, spec, kind_assets))
+    });
+    let (block, position, spec, kind_assets) = match target {
+        Some(target) => target,
+        None => {
+            ghosts.for_each_mut(|(e, _, _)| commands.entity(e).despawn_recursive());
+            return;
+        }
+    };
+
+    let valid = check_placement(
+        block,
+        selection.kind,
+        &config,
+        &surface,
+        &rules,
+        path.as_deref(),
+        &tower_assets,
+        &catalogues,
+        &resources,
+    )
+    .is_ok();
+    let ghost = PlacementGhost {
+        kind: selection.kind,
+        valid,
+    };
+
+    // Only the position changed, the ghost can be moved instead of spawned again
+    if let Some((_, current, mut transform)) = ghosts.iter_mut().next() {
+        if *current == ghost {
+            if transform.translation != position {
+                transform.translation = position;
+            }
+            return;
+        }
+    }
+    ghosts.for_each_mut(|(e, _, _)| commands.entity(e).despawn_recursive());
+
+    let (material, range_material) = if valid {
+        (&render_assets.ghost_material, &render_assets.range_material)
+    } else {
+        (
+            &render_assets.invalid_ghost_material,
+            &render_assets.invalid_ghost_material,
+        )
+    };
+    let entity = spawn_tower_ghost(&mut commands, position, spec, kind_assets, material);
+    commands
+        .entity(entity)
+        .insert(ghost)
+        .insert(StateScoped(GameState::Defense))
+        .insert(Name::new("placement_ghost"))
+        .with_children(|p| {
+            p.spawn_bundle(PbrBundle {
+                mesh: render_assets.range_mesh.clone(),
+                material: range_material.clone(),
+                transform: Transform::from_translation(Vec3::Y * (config.block_size / 2.0 + 0.1))
+                    .with_scale(Vec3::new(spec.range, 1.0, spec.range)),
+                ..Default::default()
+            });
+        });
+}
+
+#[derive(Component, PartialEq, Eq)]
+pub(super) struct PlacementGhost {
+    kind: TowerKind,
+    valid: bool,
+}
@@ -0,0 +1,23 @@
+use crate::projectile::Projectile;
+use bevy::prelude::*;
+
+#[derive(Default, Clone)]
+pub struct ProjectileAssets {
+    pub mesh: Handle<Mesh>,
+    pub material: Handle<StandardMaterial>,
+}
+
+pub(super) fn add_projectile_meshes(
+    mut commands: Commands,
+    projectile_assets: Res<ProjectileAssets>,
+    projectiles: Query<Entity, (With<Projectile>, Without<Handle<Mesh>>)>,
+) {
+    projectiles.for_each(|e| {
+        commands.entity(e).insert_bundle((
+            projectile_assets.mesh.clone(),
+            projectile_assets.material.clone(),
+            Visibility::default(),
+            ComputedVisibility::default(),
+        ));
+    });
+}
@@ -0,0 +1,208 @@
+use crate::tower::{
+    part_transforms, Tower, TowerAssets, TowerBody, TowerCannon, TowerCatalogue, TowerKind,
+    TowerSpec, TowerUpgraded,
+};
+use bevy::prelude::*;
+use bevy_mod_picking::{PickableBundle, PickableButton};
+use std::collections::HashMap;
+
+#[derive(Default, Clone)]
+pub struct TowerRenderAssets {
+    /// Filled in once the catalogue is loaded
+    pub kinds: HashMap<TowerKind, TowerKindAssets>,
+    /// Flat ring of radius 1, scaled to show the range of a tower
+    pub range_mesh: Handle<Mesh>,
+    pub range_material: Handle<StandardMaterial>,
+    /// Used for the placement preview, depending on whether the tower can be built
+    pub ghost_material: Handle<StandardMaterial>,
+    pub invalid_ghost_material: Handle<StandardMaterial>,
+}
+
+#[derive(Default, Clone)]
+pub struct TowerKindAssets {
+    pub cannon_mesh: Handle<Mesh>,
+    pub body_mesh: Handle<Mesh>,
+    pub material: Handle<StandardMaterial>,
+    /// Material of every tier, indexed by upgrade path then by tier, from the first upgrade on
+    pub upgrade_materials: Vec<Vec<Handle<StandardMaterial>>>,
+}
+
+impl TowerKindAssets {
+    pub fn tier_material(
+        &self,
+        path: Option<usize>,
+        tier: usize,
+    ) -> Option<&Handle<StandardMaterial>> {
+        match (path, tier) {
+            (_, 0) => Some(&self.material),
+            (Some(path), tier) => self.upgrade_materials.get(path)?.get(tier - 1),
+            (None, _) => None,
+        }
+    }
+}
+
+/// Loads the meshes and materials of every kind of tower in the catalogue, and reloads them
+/// whenever the catalogue changes
+pub(super) fn load_kind_assets(
+    mut events: EventReader<AssetEvent<TowerCatalogue>>,
+    asset_server: Res<AssetServer>,
+    catalogues: Res<Assets<TowerCatalogue>>,
+    tower_assets: Res<TowerAssets>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut render_assets: ResMut<TowerRenderAssets>,
+) {
+    for event in events.iter() {
+        let handle = match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle,
+            AssetEvent::Removed { .. } => continue,
+        };
+        if *handle != tower_assets.catalogue {
+            continue;
+        }
+        if let Some(catalogue) = catalogues.get(handle) {
+            render_assets.kinds = catalogue
+                .towers
+                .iter()
+                .map(|(kind, spec)| {
+                    let (r, g, b) = spec.color;
+                    // Tiers without a color of their own keep the one of the tier before them
+                    let upgrade_materials = spec
+                        .upgrades
+                        .iter()
+                        .map(|path| {
+                            let mut color = spec.color;
+                            path.tiers
+                                .iter()
+                                .map(|tier| {
+                                    color = tier.color.unwrap_or(color);
+                                    materials.add(Color::rgb(color.0, color.1, color.2).into())
+                                })
+                                .collect()
+                        })
+                        .collect();
+                    let assets = TowerKindAssets {
+                        cannon_mesh: asset_server.load(spec.cannon_mesh.as_str()),
+                        body_mesh: asset_server.load(spec.body_mesh.as_str()),
+                        material: materials.add(Color::rgb(r, g, b).into()),
+                        upgrade_materials,
+                    };
+                    (*kind, assets)
+                })
+                .collect();
+        }
+    }
+}
+
+/// Gives the parts of new towers the meshes of their kind and the material of their tier, once
+/// those are loaded
+#[allow(clippy::type_complexity)]
+pub(super) fn add_tower_meshes(
+    mut commands: Commands,
+    render_assets: Res<TowerRenderAssets>,
+    towers: Query<&Tower>,
+    parts: Query<
+        (Entity, &Parent, Option<&TowerCannon>),
+        (
+            Or<(With<TowerCannon>, With<TowerBody>)>,
+            Without<Handle<Mesh>>,
+        ),
+    >,
+) {
+    for (entity, parent, cannon) in parts.iter() {
+        let (tower, kind_assets) = match towers
+            .get(parent.0)
+            .ok()
+            .and_then(|tower| Some((tower, render_assets.kinds.get(&tower.kind)?)))
+        {
+            Some(found) => found,
+            None => continue,
+        };
+        let mesh = if cannon.is_some() {
+            &kind_assets.cannon_mesh
+        } else {
+            &kind_assets.body_mesh
+        };
+        let material = kind_assets
+            .tier_material(tower.upgrade_path, tower.tier)
+            .unwrap_or(&kind_assets.material);
+        commands
+            .entity(entity)
+            .insert_bundle((
+                mesh.clone(),
+                material.clone(),
+                Visibility::default(),
+                ComputedVisibility::default(),
+            ))
+            .insert_bundle(PickableBundle::default());
+    }
+}
+
+/// Gives the parts of upgraded towers the material of their new tier
+#[allow(clippy::type_complexity)]
+pub(super) fn show_upgrades(
+    mut events: EventReader<TowerUpgraded>,
+    render_assets: Res<TowerRenderAssets>,
+    towers: Query<(&Tower, &Children)>,
+    mut parts: Query<
+        (
+            &mut Handle<StandardMaterial>,
+            Option<&mut PickableButton<StandardMaterial>>,
+        ),
+        Or<(With<TowerCannon>, With<TowerBody>)>,
+    >,
+) {
+    for TowerUpgraded { tower, .. } in events.iter() {
+        let (tower, children) = match towers.get(*tower) {
+            Ok(tower) => tower,
+            Err(_) => continue,
+        };
+        let material = match render_assets
+            .kinds
+            .get(&tower.kind)
+            .and_then(|assets| assets.tier_material(tower.upgrade_path, tower.tier))
+        {
+            Some(material) => material,
+            None => continue,
+        };
+        for child in children.iter() {
+            if let Ok((mut handle, button)) = parts.get_mut(*child) {
+                *handle = material.clone();
+                // Picking restores this material once the part is no longer hovered
+                if let Some(mut button) = button {
+                    button.initial = Some(material.clone());
+                }
+            }
+        }
+    }
+}
+
+/// Spawns a see-through tower without any behaviour, to preview where a tower would be built
+pub(super) fn spawn_tower_ghost(
+    commands: &mut Commands,
+    position: Vec3,
+    spec: &TowerSpec,
+    kind_assets: &TowerKindAssets,
+    material: &Handle<StandardMaterial>,
+) -> Entity {
+    let (cannon, body) = part_transforms(spec, 0);
+    commands
+        .spawn_bundle((
+            Transform::from_translation(position),
+            GlobalTransform::default(),
+        ))
+        .with_children(|p| {
+            p.spawn_bundle(PbrBundle {
+                mesh: kind_assets.cannon_mesh.clone(),
+                material: material.clone(),
+                transform: cannon,
+                ..Default::default()
+            });
+            p.spawn_bundle(PbrBundle {
+                mesh: kind_assets.body_mesh.clone(),
+                material: material.clone(),
+                transform: body,
+                ..Default::default()
+            });
+        })
+        .id()
+}
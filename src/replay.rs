@@ -0,0 +1,268 @@
+use crate::{
+    game_speed::GameSpeed,
+    game_state::GameState,
+    map::{ChunkMap, MapSeed, MapSystem},
+    save::LoadedSave,
+    sim::{CommandSystem, PlayerCommand, PlayerCommands, SimTime, SimulationApp},
+};
+use anyhow::{bail, Context};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+// https://github.com/Leafwing-Studios/leafwing-input-manager/blob/446ac84cfcd2c76ae5607cca1c871681af09a0d9/src/lib.rs#L98
+#[derive(Default)]
+pub struct ReplayPlugin {
+    desired_state: Option<GameState>,
+}
+
+impl ReplayPlugin {
+    pub fn new() -> Self {
+        Self {
+            desired_state: None,
+        }
+    }
+
+    pub fn run_in_state(state: GameState) -> Self {
+        Self {
+            desired_state: Some(state),
+        }
+    }
+}
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        if let Some(desired_state) = self.desired_state {
+            app.add_startup_system(watch_replay_from_args)
+                .add_system_set(
+                    SystemSet::on_enter(desired_state)
+                        .with_system(start_recording.after(MapSystem::Setup)),
+                )
+                .add_command_system_set(
+                    SystemSet::new()
+                        .before(CommandSystem::Apply)
+                        .with_system(play_commands.label(ReplaySystem::Play))
+                        .with_system(record_commands.after(ReplaySystem::Play)),
+                )
+                .add_system_set(SystemSet::on_exit(desired_state).with_system(finish_replay));
+        } else {
+            panic!("ReplayPlugin::run_in_state() must be called with a GameState");
+        }
+    }
+}
+
+#[derive(SystemLabel, Clone, Hash, Debug, PartialEq, Eq)]
+enum ReplaySystem {
+    Play,
+}
+
+/// Replays written with any other version of the format are not played
+pub const REPLAY_VERSION: u32 = 1;
+
+const REPLAY_DIR: &str = "replays";
+const LAST_REPLAY_FILE: &str = "last.ron";
+
+/// Everything needed to play a game again, step for step: the seed of its map and every command
+/// of the player. Towers and waves are read from the assets, a replay only plays out the same way
+/// with the assets it was recorded with.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Replay {
+    pub version: u32,
+    pub seed: u64,
+    /// The commands of every command pass that had any, with the [`SimTime::tick`] it ran at.
+    /// Several passes can run at the same tick while the game is stopped.
+    pub passes: Vec<(u64, Vec<PlayerCommand>)>,
+}
+
+/// Read before the rest of the replay, so that replays of other versions are reported as such
+/// rather than as broken
+#[derive(Deserialize)]
+struct ReplayHeader {
+    version: u32,
+}
+
+impl Replay {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            version: REPLAY_VERSION,
+            seed,
+            passes: Vec::new(),
+        }
+    }
+
+    /// Where the last game played is recorded
+    pub fn last_path() -> PathBuf {
+        PathBuf::from(REPLAY_DIR).join(LAST_REPLAY_FILE)
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Could not read {}", path.display()))?;
+        let header: ReplayHeader = ron::from_str(&text).context("Not a replay")?;
+        if header.version != REPLAY_VERSION {
+            bail!(
+                "Replay version {} is not supported, expected {}",
+                header.version,
+                REPLAY_VERSION
+            );
+        }
+        Ok(ron::from_str(&text)?)
+    }
+
+    /// Written on a single line, replays of long games get big
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text = ron::to_string(self)?;
+        fs::write(path, text).with_context(|| format!("Could not write {}", path.display()))
+    }
+}
+
+/// Replay played back instead of the commands of the player, who only watches
+pub struct ReplayPlayback {
+    replay: Replay,
+    /// Index of the next pass to play
+    next: usize,
+}
+
+impl ReplayPlayback {
+    pub fn new(replay: Replay) -> Self {
+        Self { replay, next: 0 }
+    }
+
+    /// Every command was played, the game goes on without any
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.replay.passes.len()
+    }
+
+    /// Whether the next pass is to be played at `tick`
+    fn is_due(&self, tick: u64) -> bool {
+        matches!(self.replay.passes.get(self.next), Some((t, _)) if *t <= tick)
+    }
+}
+
+/// Loads a replay and plays it back in a new game
+pub fn watch_replay(
+    path: &Path,
+    commands: &mut Commands,
+    seed: &mut MapSeed,
+    game_state: &mut State<GameState>,
+) {
+    match Replay::load(path) {
+        Ok(replay) => {
+            // The map is generated again from the recorded seed
+            *seed = MapSeed(replay.seed);
+            commands.insert_resource(ReplayPlayback::new(replay));
+            if let Err(e) = game_state.set(GameState::Defense) {
+                warn!("Could not start the replay: {}", e);
+            }
+        }
+        Err(e) => warn!("Could not load the replay: {:#}", e),
+    }
+}
+
+/// Replay of the game being played
+struct Recording {
+    replay: Replay,
+    /// Last speed written down
+    speed: f32,
+}
+
+/// `yatd --replay <file>` plays a replay back instead of showing the start menu
+fn watch_replay_from_args(
+    mut commands: Commands,
+    mut seed: ResMut<MapSeed>,
+    mut game_state: ResMut<State<GameState>>,
+) {
+    if let Some(path) = env::args().skip_while(|arg| arg != "--replay").nth(1) {
+        watch_replay(Path::new(&path), &mut commands, &mut seed, &mut game_state);
+    }
+}
+
+/// Records new games only, the seed is read once the map has settled on it. Continued games
+/// start from a save rather than from their seed, and replays are not recorded again. Games whose
+/// map could not be set up are not recorded either, the last replay is kept.
+fn start_recording(
+    mut commands: Commands,
+    chunk_map: Res<ChunkMap>,
+    seed: Res<MapSeed>,
+    speed: Res<GameSpeed>,
+    save: Option<Res<LoadedSave>>,
+    playback: Option<Res<ReplayPlayback>>,
+) {
+    if save.is_none() && playback.is_none() && !chunk_map.is_empty() {
+        commands.insert_resource(Recording {
+            replay: Replay::new(seed.0),
+            speed: speed.factor(),
+        });
+    }
+}
+
+/// Replaces the commands of the player with those recorded for the current tick
+fn play_commands(
+    playback: Option<ResMut<ReplayPlayback>>,
+    mut time: ResMut<SimTime>,
+    mut commands: ResMut<PlayerCommands>,
+) {
+    let mut playback = match playback {
+        Some(playback) => playback,
+        None => return,
+    };
+    commands.0.clear();
+    if !playback.is_due(time.tick()) {
+        return;
+    }
+    let pass = playback.replay.passes[playback.next].1.clone();
+    commands.0.extend(pass);
+    playback.next += 1;
+    // The next pass may need the towers this one builds, they are only spawned once it is over
+    if playback.is_due(time.tick()) {
+        time.hold();
+    } else if playback.is_finished() {
+        info!("Every command of the replay was played");
+    }
+}
+
+fn record_commands(
+    recording: Option<ResMut<Recording>>,
+    time: Res<SimTime>,
+    speed: Res<GameSpeed>,
+    commands: Res<PlayerCommands>,
+) {
+    let mut recording = match recording {
+        Some(recording) => recording,
+        None => return,
+    };
+    let mut pass = commands.0.clone();
+    // Only changes how fast the replay is shown, not how it plays out
+    if speed.factor() != recording.speed {
+        recording.speed = speed.factor();
+        pass.push(PlayerCommand::SetSpeed(speed.factor()));
+    }
+    if !pass.is_empty() {
+        recording.replay.passes.push((time.tick(), pass));
+    }
+}
+
+/// Writes the replay of the game that was left, however it ended
+fn finish_replay(
+    mut commands: Commands,
+    recording: Option<Res<Recording>>,
+    playback: Option<Res<ReplayPlayback>>,
+) {
+    if let Some(recording) = recording {
+        let path = Replay::last_path();
+        match recording.replay.write(&path) {
+            Ok(()) => info!("Replay written to {}", path.display()),
+            Err(e) => warn!("Could not write the replay: {:#}", e),
+        }
+        commands.remove_resource::<Recording>();
+    }
+    if playback.is_some() {
+        commands.remove_resource::<ReplayPlayback>();
+    }
+}
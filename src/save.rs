@@ -0,0 +1,322 @@
+use crate::{
+    economy::{GameStats, PlayerResources},
+    game_state::GameState,
+    map::{self, Block, Chunk, ChunkMap, ExpandMap, MapConfig, MapError, MapSeed},
+    replay::ReplayPlayback,
+    tower::{TargetPriority, Tower, TowerAssets, TowerCatalogue, TowerKind},
+    wave::{WaveCleared, WaveState},
+};
+use anyhow::{bail, Context};
+use bevy::{ecs::system::SystemParam, prelude::*};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf};
+
+// https://github.com/Leafwing-Studios/leafwing-input-manager/blob/446ac84cfcd2c76ae5607cca1c871681af09a0d9/src/lib.rs#L98
+#[derive(Default)]
+pub struct SavePlugin {
+    desired_state: Option<GameState>,
+}
+
+impl SavePlugin {
+    pub fn new() -> Self {
+        Self {
+            desired_state: None,
+        }
+    }
+
+    pub fn run_in_state(state: GameState) -> Self {
+        Self {
+            desired_state: Some(state),
+        }
+    }
+}
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        if let Some(desired_state) = self.desired_state {
+            app.add_system_set(SystemSet::on_enter(desired_state).with_system(expand_saved_map))
+                .add_system_set(
+                    SystemSet::on_update(desired_state)
+                        .with_system(restore_game)
+                        .with_system(save_between_waves),
+                )
+                .add_system_set(SystemSet::on_exit(desired_state).with_system(save_on_exit));
+        } else {
+            panic!("SavePlugin::run_in_state() must be called with a GameState");
+        }
+    }
+}
+
+/// Saves written with any other version of the format are not loaded
+pub const SAVE_VERSION: u32 = 1;
+
+const SAVE_DIR: &str = "saves";
+const SAVE_FILE: &str = "savegame.ron";
+
+/// Everything needed to continue a game. Enemies and projectiles are not saved, a wave that was
+/// under way is started again from the beginning.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SaveGame {
+    pub version: u32,
+    pub seed: u64,
+    /// Coordinates of the chunks of the map, see [`ChunkMap::expansion_order`]
+    pub chunks: Vec<(i32, i32)>,
+    pub towers: Vec<SavedTower>,
+    pub resources: PlayerResources,
+    pub stats: GameStats,
+    /// Index of the wave to start next
+    pub next_wave: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SavedTower {
+    pub kind: TowerKind,
+    /// World cell of the column the tower stands on, see [`MapConfig::cell_at`]
+    pub cell: (i32, i32),
+    pub priority: TargetPriority,
+    pub upgrade_path: Option<usize>,
+    pub tier: usize,
+    pub kills: u32,
+    pub damage_dealt: f32,
+}
+
+/// Read before the rest of the save, so that saves of other versions are reported as such rather
+/// than as broken
+#[derive(Deserialize)]
+struct SaveHeader {
+    version: u32,
+}
+
+impl SaveGame {
+    pub fn path() -> PathBuf {
+        PathBuf::from(SAVE_DIR).join(SAVE_FILE)
+    }
+
+    pub fn exists() -> bool {
+        Self::path().is_file()
+    }
+
+    pub fn load() -> anyhow::Result<Self> {
+        let path = Self::path();
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("Could not read {}", path.display()))?;
+        let header: SaveHeader = ron::from_str(&text).context("Not a save")?;
+        if header.version != SAVE_VERSION {
+            bail!(
+                "Save version {} is not supported, expected {}",
+                header.version,
+                SAVE_VERSION
+            );
+        }
+        Ok(ron::from_str(&text)?)
+    }
+
+    pub fn write(&self) -> anyhow::Result<()> {
+        let path = Self::path();
+        fs::create_dir_all(SAVE_DIR)?;
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        fs::write(&path, text).with_context(|| format!("Could not write {}", path.display()))
+    }
+
+    /// Removes the save once the game it belongs to is over
+    pub fn delete() -> anyhow::Result<()> {
+        if Self::exists() {
+            fs::remove_file(Self::path())?;
+        }
+        Ok(())
+    }
+}
+
+/// Save to restore when [`GameState::Defense`] is entered. The [`MapSeed`] must be set to the
+/// save's seed as well.
+pub struct LoadedSave(pub SaveGame);
+
+/// The parts of the world a [`SaveGame`] is made of
+#[derive(SystemParam)]
+struct GameSnapshot<'w, 's> {
+    /// Replays are watched, they must not replace the game the player saved
+    playback: Option<Res<'w, ReplayPlayback>>,
+    seed: Res<'w, MapSeed>,
+    config: Res<'w, MapConfig>,
+    chunk_map: Res<'w, ChunkMap>,
+    resources: Res<'w, PlayerResources>,
+    stats: Res<'w, GameStats>,
+    wave_state: Res<'w, WaveState>,
+    towers: Query<'w, 's, (&'static Tower, &'static GlobalTransform)>,
+}
+
+impl<'w, 's> GameSnapshot<'w, 's> {
+    fn save_game(&self) -> SaveGame {
+        let towers = self
+            .towers
+            .iter()
+            .map(|(tower, transform)| {
+                let cell = self.config.cell_at(transform.translation);
+                SavedTower {
+                    kind: tower.kind,
+                    cell: (cell.x, cell.y),
+                    priority: tower.priority,
+                    upgrade_path: tower.upgrade_path,
+                    tier: tower.tier,
+                    kills: tower.kills,
+                    damage_dealt: tower.damage_dealt,
+                }
+            })
+            .collect();
+        // Waves under way are played again from their start
+        let next_wave = self
+            .wave_state
+            .active
+            .iter()
+            .copied()
+            .min()
+            .unwrap_or(self.wave_state.next);
+
+        SaveGame {
+            version: SAVE_VERSION,
+            seed: self.seed.0,
+            chunks: self
+                .chunk_map
+                .expansion_order()
+                .into_iter()
+                .map(|c| (c.x, c.y))
+                .collect(),
+            towers,
+            resources: self.resources.clone(),
+            stats: self.stats.clone(),
+            next_wave,
+        }
+    }
+
+    fn is_over(&self) -> bool {
+        self.resources.lives == 0 || self.wave_state.is_finished()
+    }
+}
+
+/// Spawns the chunks the saved map was expanded to, the first one is spawned by the map itself
+fn expand_saved_map(save: Option<Res<LoadedSave>>, mut events: EventWriter<ExpandMap>) {
+    if let Some(save) = save {
+        for (x, y) in save.0.chunks.iter().skip(1) {
+            events.send(ExpandMap {
+                coord: IVec2::new(*x, *y),
+            });
+        }
+    }
+}
+
+/// Puts the towers, gold and wave progress of the save back, once the map and the tower catalogue
+/// are ready
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn restore_game(
+    mut commands: Commands,
+    save: Option<Res<LoadedSave>>,
+    config: Res<MapConfig>,
+    chunk_map: Res<ChunkMap>,
+    tower_assets: Res<TowerAssets>,
+    catalogues: Res<Assets<TowerCatalogue>>,
+    mut resources: ResMut<PlayerResources>,
+    mut stats: ResMut<GameStats>,
+    mut wave_state: ResMut<WaveState>,
+    chunks: Query<(), With<Chunk>>,
+    mut blocks: Query<(&GlobalTransform, &mut Block)>,
+) {
+    let save = match save {
+        Some(save) => save,
+        None => return,
+    };
+    // Chunks are spawned through commands, their blocks only exist once those are applied
+    let map_ready = save.0.chunks.iter().all(|(x, y)| {
+        matches!(chunk_map.get(IVec2::new(*x, *y)), Some(chunk) if chunks.get(chunk).is_ok())
+    });
+    let towers_ready = save
+        .0
+        .towers
+        .iter()
+        .all(|saved| tower_assets.spec(&catalogues, saved.kind).is_some());
+    if !map_ready || !towers_ready {
+        return;
+    }
+
+    for saved in save.0.towers.iter() {
+        let spec = match tower_assets.spec(&catalogues, saved.kind) {
+            Some(spec) => spec,
+            None => continue,
+        };
+        let mut tower = Tower::from_spec(saved.kind, spec);
+        if let Some(path) = saved.upgrade_path {
+            tower.restore_upgrades(spec, path, saved.tier);
+        }
+        tower.priority = saved.priority;
+        tower.kills = saved.kills;
+        tower.damage_dealt = saved.damage_dealt;
+
+        let cell = IVec2::new(saved.cell.0, saved.cell.1);
+        let restored = map::restore_tower(
+            &mut commands,
+            tower,
+            cell,
+            &config,
+            &tower_assets,
+            &catalogues,
+            &mut blocks,
+        );
+        if restored.is_none() {
+            warn!("Could not restore the {:?} tower on {}", saved.kind, cell);
+        }
+    }
+    *resources = save.0.resources.clone();
+    *stats = save.0.stats.clone();
+    wave_state.resume_at(save.0.next_wave);
+
+    commands.remove_resource::<LoadedSave>();
+    info!("Restored the saved game");
+}
+
+/// Saves once every started wave is cleared. Its reward has already been paid out by the
+/// simulation, which runs before the rest of the frame.
+fn save_between_waves(
+    mut cleared: EventReader<WaveCleared>,
+    save: Option<Res<LoadedSave>>,
+    snapshot: GameSnapshot,
+) {
+    if cleared.iter().count() == 0
+        || save.is_some()
+        || snapshot.playback.is_some()
+        || !snapshot.wave_state.active.is_empty()
+        || snapshot.is_over()
+    {
+        return;
+    }
+    if let Err(e) = snapshot.save_game().write() {
+        warn!("Could not save the game: {:#}", e);
+    }
+}
+
+/// Saves the game when it is left, or removes the save when the game is over
+fn save_on_exit(
+    mut commands: Commands,
+    save: Option<Res<LoadedSave>>,
+    map_error: Option<Res<MapError>>,
+    snapshot: GameSnapshot,
+) {
+    // Left before the save was restored, the one on disk is still the one to continue
+    if save.is_some() {
+        commands.remove_resource::<LoadedSave>();
+        return;
+    }
+    if snapshot.playback.is_some() {
+        return;
+    }
+    // The game never started, there is nothing to save
+    if map_error.is_some() {
+        return;
+    }
+    let result = if snapshot.is_over() {
+        SaveGame::delete()
+    } else {
+        snapshot.save_game().write()
+    };
+    if let Err(e) = result {
+        warn!("Could not save the game: {:#}", e);
+    }
+}
@@ -0,0 +1,16 @@
+/// Player preferences, edited from the pause menu
+pub struct Settings {
+    /// Multiplies the speed at which the camera moves
+    pub camera_speed: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self { camera_speed: 1.0 }
+    }
+}
+
+impl Settings {
+    pub const MIN_CAMERA_SPEED: f32 = 0.25;
+    pub const MAX_CAMERA_SPEED: f32 = 4.0;
+}
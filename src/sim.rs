@@ -0,0 +1,324 @@
+use crate::{
+    game_speed::GameSpeed,
+    game_state::GameState,
+    map::{ChunkMap, MapSystem},
+    tower::{TargetPriority, TowerKind},
+};
+use bevy::{
+    ecs::schedule::{Stage, SystemStage},
+    prelude::*,
+    transform::transform_propagate_system::transform_propagate_system,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, time::Duration};
+
+pub mod headless;
+
+// https://github.com/Leafwing-Studios/leafwing-input-manager/blob/446ac84cfcd2c76ae5607cca1c871681af09a0d9/src/lib.rs#L98
+#[derive(Default)]
+pub struct SimulationPlugin {
+    desired_state: Option<GameState>,
+}
+
+impl SimulationPlugin {
+    pub fn new() -> Self {
+        Self {
+            desired_state: None,
+        }
+    }
+
+    pub fn run_in_state(state: GameState) -> Self {
+        Self {
+            desired_state: Some(state),
+        }
+    }
+}
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SimTime>()
+            .init_resource::<PlayerCommands>()
+            .init_resource::<SimReadiness>()
+            .add_command_system_set(
+                SystemSet::new().with_system(clear_commands.after(CommandSystem::Apply)),
+            )
+            .add_sim_system_set(
+                SystemSet::new()
+                    .label(SimSystem::Propagate)
+                    .with_system(transform_propagate_system),
+            );
+        if let Some(desired_state) = self.desired_state {
+            app.add_system_set(
+                SystemSet::on_enter(desired_state).with_system(setup.after(MapSystem::Setup)),
+            )
+            .add_system_set(
+                SystemSet::on_update(desired_state).with_system(run_simulation.exclusive_system()),
+            );
+        } else {
+            panic!("SimulationPlugin::run_in_state() must be called with a GameState");
+        }
+    }
+}
+
+/// Game time simulated by a single step
+pub const SIM_STEP: f32 = 1.0 / 60.0;
+
+/// Steps run in a single frame at most. When frames take longer than that, the game slows down
+/// rather than spending even longer catching up.
+const MAX_STEPS_PER_FRAME: u32 = 32;
+
+/// Combat, movement and waves. Its systems are run one after the other, in an order that only
+/// depends on how they were added, as many times per frame as the [`GameSpeed`] calls for and
+/// always with the same [`SIM_STEP`]. The same inputs therefore always give the same game.
+///
+/// The inputs are the [`PlayerCommands`], carried out by a command pass that runs before every
+/// step. Nothing else the player does changes how the game plays out.
+pub struct Simulation {
+    commands: SystemStage,
+    stage: SystemStage,
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Self {
+            commands: SystemStage::single_threaded(),
+            stage: SystemStage::single_threaded(),
+        }
+    }
+}
+
+impl Simulation {
+    /// Carries out the queued [`PlayerCommands`], then runs a single step, whatever the speed of
+    /// the game
+    pub fn step(world: &mut World) {
+        Self::apply_commands(world);
+        world.resource_scope(|world, mut simulation: Mut<Simulation>| {
+            simulation.stage.run(world);
+        });
+        world.get_resource_mut::<SimTime>().unwrap().tick += 1;
+    }
+
+    /// Whether every asset the steps need is loaded, see [`SimReadiness`]
+    pub fn is_ready(world: &World) -> bool {
+        world.get_resource::<SimReadiness>().unwrap().is_ready()
+    }
+
+    /// Carries out the queued [`PlayerCommands`] without running a step, so that they also take
+    /// effect while the game is stopped
+    pub fn apply_commands(world: &mut World) {
+        world.resource_scope(|world, mut simulation: Mut<Simulation>| loop {
+            simulation.commands.run(world);
+            let mut time = world.get_resource_mut::<SimTime>().unwrap();
+            if !std::mem::take(&mut time.held) {
+                break;
+            }
+        });
+    }
+}
+
+pub trait SimulationApp {
+    /// Adds systems to every step of the [`Simulation`]
+    fn add_sim_system_set(&mut self, system_set: SystemSet) -> &mut Self;
+
+    /// Adds systems to the command pass run before every step of the [`Simulation`]
+    fn add_command_system_set(&mut self, system_set: SystemSet) -> &mut Self;
+
+    /// Holds the [`Simulation`] back until `asset` is reported loaded, see [`SimReadiness`]
+    fn wait_for_asset(&mut self, asset: &'static str) -> &mut Self;
+}
+
+impl SimulationApp for App {
+    fn add_sim_system_set(&mut self, system_set: SystemSet) -> &mut Self {
+        simulation(self).stage.add_system_set(system_set);
+        self
+    }
+
+    fn add_command_system_set(&mut self, system_set: SystemSet) -> &mut Self {
+        simulation(self).commands.add_system_set(system_set);
+        self
+    }
+
+    fn wait_for_asset(&mut self, asset: &'static str) -> &mut Self {
+        self.init_resource::<SimReadiness>();
+        self.world
+            .get_resource_mut::<SimReadiness>()
+            .unwrap()
+            .report(asset, false);
+        self
+    }
+}
+
+fn simulation(app: &mut App) -> Mut<'_, Simulation> {
+    if !app.world.contains_resource::<Simulation>() {
+        app.world.insert_resource(Simulation::default());
+    }
+    app.world.get_resource_mut::<Simulation>().unwrap()
+}
+
+/// Assets the [`Simulation`] waits for, each one reported by the plugin that loads it. No step is
+/// run before all of them are loaded, so that a game starts at the same step however long loading
+/// takes.
+#[derive(Default)]
+pub struct SimReadiness {
+    /// Whether each asset is loaded, by name
+    assets: HashMap<&'static str, bool>,
+}
+
+impl SimReadiness {
+    pub fn report(&mut self, asset: &'static str, loaded: bool) {
+        self.assets.insert(asset, loaded);
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.assets.values().all(|loaded| *loaded)
+    }
+
+    /// Assets still loading, in a stable order
+    pub fn waiting(&self) -> Vec<&'static str> {
+        let mut waiting: Vec<_> = self
+            .assets
+            .iter()
+            .filter(|(_, loaded)| !**loaded)
+            .map(|(asset, _)| *asset)
+            .collect();
+        waiting.sort_unstable();
+        waiting
+    }
+}
+
+/// Parts of the command pass
+#[derive(SystemLabel, Clone, Hash, Debug, PartialEq, Eq)]
+pub enum CommandSystem {
+    /// Carries out the [`PlayerCommands`]. Systems that fill or read the queue run before it,
+    /// systems reacting to what the commands did run after it.
+    Apply,
+}
+
+/// Something the player asked for. Towers are referred to by the cell they stand on, which,
+/// unlike their entity, is the same every time a game is played.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum PlayerCommand {
+    Build {
+        kind: TowerKind,
+        cell: (i32, i32),
+    },
+    Upgrade {
+        cell: (i32, i32),
+        path: usize,
+    },
+    Sell {
+        cell: (i32, i32),
+    },
+    SetPriority {
+        cell: (i32, i32),
+        priority: TargetPriority,
+    },
+    CallNextWave,
+    /// Buys the chunk at these coordinates, see [`crate::map::ChunkMap::can_expand_to`]
+    Expand {
+        chunk: (i32, i32),
+    },
+    /// The player changes the [`GameSpeed`] directly, these only come from replays
+    SetSpeed(f32),
+}
+
+/// Commands waiting for the next command pass of the [`Simulation`]
+#[derive(Default)]
+pub struct PlayerCommands(pub Vec<PlayerCommand>);
+
+impl PlayerCommands {
+    pub fn push(&mut self, command: PlayerCommand) {
+        self.0.push(command);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &PlayerCommand> {
+        self.0.iter()
+    }
+}
+
+/// Parts of a step, in the order they run
+#[derive(SystemLabel, Clone, Hash, Debug, PartialEq, Eq)]
+pub enum SimSystem {
+    /// Brings every `GlobalTransform` up to date with the previous step
+    Propagate,
+    Waves,
+    Movement,
+    Combat,
+    /// Reacts to what happened during the step
+    Stats,
+}
+
+/// Replaces [`Time`] in the systems of the [`Simulation`]
+#[derive(Default)]
+pub struct SimTime {
+    tick: u64,
+    /// Game time not simulated yet
+    accumulated: f32,
+    /// The command pass runs again before the next step
+    held: bool,
+}
+
+impl SimTime {
+    /// Steps run since the game started
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Runs the command pass once more before the next step, for commands that need the entities
+    /// spawned by the ones before them
+    pub fn hold(&mut self) {
+        self.held = true;
+    }
+
+    pub fn delta(&self) -> Duration {
+        Duration::from_secs_f32(SIM_STEP)
+    }
+
+    pub fn delta_seconds(&self) -> f32 {
+        SIM_STEP
+    }
+
+    pub fn elapsed_seconds(&self) -> f32 {
+        self.tick as f32 * SIM_STEP
+    }
+}
+
+/// Skipped when the map could not be set up, the game is left before it starts
+fn setup(
+    chunk_map: Res<ChunkMap>,
+    mut time: ResMut<SimTime>,
+    mut commands: ResMut<PlayerCommands>,
+) {
+    if chunk_map.is_empty() {
+        return;
+    }
+    *time = SimTime::default();
+    commands.0.clear();
+}
+
+/// Every command is carried out by the first pass that sees it
+fn clear_commands(mut commands: ResMut<PlayerCommands>) {
+    commands.0.clear();
+}
+
+/// Runs as many steps as the time elapsed since the last frame, scaled by the [`GameSpeed`], fits
+fn run_simulation(world: &mut World) {
+    if !Simulation::is_ready(world) {
+        return;
+    }
+    let elapsed = world.get_resource::<Time>().unwrap().delta_seconds()
+        * world.get_resource::<GameSpeed>().unwrap().factor();
+    let steps = {
+        let mut time = world.get_resource_mut::<SimTime>().unwrap();
+        time.accumulated += elapsed;
+        let steps = ((time.accumulated / SIM_STEP) as u32).min(MAX_STEPS_PER_FRAME);
+        time.accumulated = (time.accumulated - steps as f32 * SIM_STEP).min(SIM_STEP);
+        steps
+    };
+    if steps == 0 {
+        Simulation::apply_commands(world);
+    }
+    for _ in 0..steps {
+        Simulation::step(world);
+    }
+}
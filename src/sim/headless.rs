@@ -0,0 +1,162 @@
+//! The gameplay plugins without any window, rendering or ui, driven one frame or one simulation
+//! step at a time. Used by `yatd-sim` and the tests.
+use super::{PlayerCommand, PlayerCommands, SimReadiness, Simulation};
+use crate::{game_speed::GameSpeed, game_state::GameState, tower::TowerAssets, wave::WaveAssets};
+use anyhow::bail;
+use bevy::{
+    asset::{AssetPlugin, HandleId, LoadState},
+    input::InputPlugin,
+    prelude::*,
+    transform::TransformPlugin,
+};
+use std::time::{Duration, Instant};
+
+/// How long [`HeadlessApp::wait_until_ready`] waits for the assets of the simulation to load
+const LOAD_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The gameplay plugins of the game, running in [`GameState::Defense`], with none of the window,
+/// rendering or ui ones. Towers and waves are loaded from `assets/` like in the game.
+///
+/// The game is paused: frames carry out the [`PlayerCommands`] but the simulation only moves on
+/// through [`HeadlessApp::step_simulation`], whatever time the frames take.
+pub fn headless_app() -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(TransformPlugin)
+        .add_plugin(InputPlugin)
+        .add_plugin(AssetPlugin)
+        .add_plugin(crate::game_state::GameStatePlugin)
+        .add_startup_system(crate::env::load_assets)
+        .add_plugin(crate::map::MapPlugin::run_in_state(GameState::Defense))
+        .add_plugin(super::SimulationPlugin::run_in_state(GameState::Defense))
+        .add_plugin(crate::tower::TowerPlugin::run_in_state(GameState::Defense))
+        .add_plugin(crate::enemy::EnemyPlugin::run_in_state(GameState::Defense))
+        .add_plugin(crate::projectile::ProjectilePlugin::run_in_state(
+            GameState::Defense,
+        ))
+        .add_plugin(crate::wave::WavePlugin::run_in_state(GameState::Defense))
+        .add_plugin(crate::economy::EconomyPlugin::run_in_state(
+            GameState::Defense,
+        ));
+    let mut speed = GameSpeed::default();
+    speed.set(0.0);
+    app.insert_resource(speed);
+    app
+}
+
+/// Drives an app built by [`headless_app`]
+pub trait HeadlessApp {
+    /// Runs `frames` updates of the app
+    fn advance_frames(&mut self, frames: usize) -> &mut Self;
+
+    /// Runs `steps` steps of the [`Simulation`], each one after the commands queued before it
+    fn step_simulation(&mut self, steps: u64) -> &mut Self;
+
+    /// Updates the app until the [`Simulation`] is ready to run. Fails if the towers or waves can
+    /// not be loaded, or if loading takes too long.
+    fn wait_until_ready(&mut self) -> anyhow::Result<&mut Self>;
+
+    fn state(&self) -> GameState;
+
+    /// Moves to `state`, whatever transition is already on its way, and runs the update that
+    /// enters it
+    fn set_state(&mut self, state: GameState) -> &mut Self;
+
+    /// Pushes `state` on top of the current one, and runs the update that enters it
+    fn push_state(&mut self, state: GameState) -> &mut Self;
+
+    /// Goes back to the state below the current one, and runs the update that resumes it
+    fn pop_state(&mut self) -> &mut Self;
+
+    /// Queues a command for the next command pass
+    fn command(&mut self, command: PlayerCommand) -> &mut Self;
+}
+
+impl HeadlessApp for App {
+    fn advance_frames(&mut self, frames: usize) -> &mut Self {
+        for _ in 0..frames {
+            self.update();
+        }
+        self
+    }
+
+    fn step_simulation(&mut self, steps: u64) -> &mut Self {
+        for _ in 0..steps {
+            Simulation::step(&mut self.world);
+        }
+        self
+    }
+
+    fn wait_until_ready(&mut self) -> anyhow::Result<&mut Self> {
+        let start = Instant::now();
+        loop {
+            self.update();
+            if Simulation::is_ready(&self.world) {
+                return Ok(self);
+            }
+            if let Some(path) = failed_asset(&self.world) {
+                bail!("Could not load {} from the assets directory", path);
+            }
+            if start.elapsed() > LOAD_TIMEOUT {
+                let readiness = self.world.get_resource::<SimReadiness>().unwrap();
+                bail!(
+                    "Still loading after {}s: {}",
+                    LOAD_TIMEOUT.as_secs(),
+                    readiness.waiting().join(", ")
+                );
+            }
+            // Assets are loaded on other threads
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    fn state(&self) -> GameState {
+        *self
+            .world
+            .get_resource::<State<GameState>>()
+            .unwrap()
+            .current()
+    }
+
+    fn set_state(&mut self, state: GameState) -> &mut Self {
+        let mut game_state = self.world.get_resource_mut::<State<GameState>>().unwrap();
+        if game_state.current() != &state {
+            game_state.overwrite_set(state).unwrap();
+        }
+        self.advance_frames(1)
+    }
+
+    fn push_state(&mut self, state: GameState) -> &mut Self {
+        let mut game_state = self.world.get_resource_mut::<State<GameState>>().unwrap();
+        game_state.overwrite_push(state).unwrap();
+        self.advance_frames(1)
+    }
+
+    fn pop_state(&mut self) -> &mut Self {
+        let mut game_state = self.world.get_resource_mut::<State<GameState>>().unwrap();
+        game_state.overwrite_pop().unwrap();
+        self.advance_frames(1)
+    }
+
+    fn command(&mut self, command: PlayerCommand) -> &mut Self {
+        self.world
+            .get_resource_mut::<PlayerCommands>()
+            .unwrap()
+            .push(command);
+        self
+    }
+}
+
+/// Path of the tower catalogue or wave schedule, if one of them failed to load
+fn failed_asset(world: &World) -> Option<String> {
+    let asset_server = world.get_resource::<AssetServer>().unwrap();
+    let catalogue = world.get_resource::<TowerAssets>().unwrap().catalogue.id;
+    let schedule = world.get_resource::<WaveAssets>().unwrap().schedule.id;
+    [catalogue, schedule]
+        .into_iter()
+        .find(|id| asset_server.get_load_state(*id) == LoadState::Failed)
+        .map(|id: HandleId| match asset_server.get_handle_path(id) {
+            Some(path) => path.path().display().to_string(),
+            None => format!("{:?}", id),
+        })
+}
@@ -1,8 +1,13 @@
 use bevy_tweening::{lens::*, *};
 use std::time::Duration;
 
-use crate::game_state::GameState;
-use bevy::{prelude::*, ui::FocusPolicy};
+use crate::{
+    game_state::{GameState, StateScoped},
+    map::{self, MapError, MapSeed},
+    replay::{self, Replay},
+    save::{LoadedSave, SaveGame},
+};
+use bevy::prelude::*;
 
 #[derive(Default)]
 pub struct StartMenuPlugin {
@@ -25,20 +30,32 @@ impl StartMenuPlugin {
 
 impl Plugin for StartMenuPlugin {
     fn build(&self, app: &mut App) {
+        app.init_resource::<SaveAvailable>();
         if let Some(desired_state) = self.desired_state {
-            app.add_system_set(SystemSet::on_enter(desired_state).with_system(setup))
-                .add_system_set(SystemSet::on_update(desired_state).with_system(button_selection))
-                .add_system_set(SystemSet::on_exit(desired_state).with_system(destroy));
+            app.add_system_set(
+                SystemSet::on_enter(desired_state)
+                    .with_system(setup)
+                    .with_system(check_save),
+            )
+            .add_system_set(
+                SystemSet::on_update(desired_state)
+                    .with_system(button_selection)
+                    .with_system(edit_seed)
+                    .with_system(update_seed_text)
+                    .with_system(update_continue_button),
+            );
         } else {
             panic!("StartMenuPlugin::run_in_state() must be called with a GameState");
         }
     }
 }
 
-fn setup(mut commands: Commands, asset_server: Res<AssetServer>, state: ResMut<State<GameState>>) {
-    commands.spawn_bundle(UiCameraBundle::default());
-    //.insert(StartMenuEntity {});
-
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    seed: Res<MapSeed>,
+    map_error: Option<Res<MapError>>,
+) {
     let font = asset_server.load("fonts/FiraMono-Regular.ttf");
 
     let container = commands
@@ -58,20 +75,45 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>, state: ResMut<S
             color: Color::rgba(1.0, 0.0, 1.0, 0.5).into(),
             ..Default::default()
         })
-        .insert(StartMenuEntityHIDE {})
+        .insert(StateScoped(GameState::StartMenu))
         .insert(Name::new("menu"))
         .id();
 
     let buttons = &[
         ("Continue", ButtonAction::Continue),
         ("New Game", ButtonAction::NewGame),
+        ("Last Replay", ButtonAction::WatchReplay),
         ("Quit", ButtonAction::Quit),
     ];
 
+    let mut children = Vec::new();
+    // The last game could not start, the player has to pick another seed
+    if let Some(error) = map_error {
+        let text = commands
+            .spawn_bundle(TextBundle {
+                style: Style {
+                    margin: Rect::all(Val::Px(8.)),
+                    ..Default::default()
+                },
+                text: Text::with_section(
+                    error.to_string(),
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 20.0,
+                        color: Color::rgb(0.8, 0.3, 0.3),
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            })
+            .insert(Name::new("map_error"))
+            .id();
+        children.push(text);
+        commands.remove_resource::<MapError>();
+    }
     for (text, button_action) in buttons {
-        commands
+        let button = commands
             .spawn_bundle(ButtonBundle {
-                focus_policy: FocusPolicy::Pass, // TODO: Remove once 3d picking works
                 node: Node {
                     size: Vec2::new(300., 80.),
                 },
@@ -90,9 +132,6 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>, state: ResMut<S
                 ..Default::default()
             })
             .insert(Name::new(format!("button:{}", text)))
-            .insert(Parent(container))
-            //.insert(StartMenuEntity {})
-            .insert(StartMenuEntityHIDE {})
             .insert(Animator::new(Tween::new(
                 EaseFunction::BounceOut,
                 TweeningType::Once,
@@ -104,45 +143,77 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>, state: ResMut<S
             )))
             .insert(button_action.clone())
             .with_children(|parent| {
-                parent
-                    .spawn_bundle(TextBundle {
-                        text: Text::with_section(
-                            text.to_string(),
-                            TextStyle {
-                                font: font.clone(),
-                                font_size: 48.0,
-                                color: Color::rgb(0.8, 0.8, 0.8),
-                            },
-                            TextAlignment {
-                                vertical: VerticalAlign::Center,
-                                horizontal: HorizontalAlign::Center,
-                            },
-                        ),
-                        ..Default::default()
-                    })
-                    .insert(StartMenuEntityHIDE {});
-            });
+                parent.spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        text.to_string(),
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: 48.0,
+                            color: Color::rgb(0.8, 0.8, 0.8),
+                        },
+                        TextAlignment {
+                            vertical: VerticalAlign::Center,
+                            horizontal: HorizontalAlign::Center,
+                        },
+                    ),
+                    ..Default::default()
+                });
+            })
+            .id();
+        children.push(button);
     }
-    //state.set(GameState::Defense).unwrap();
-}
 
-fn destroy(
-    mut commands: Commands,
-    query: Query<Entity, With<StartMenuEntity>>,
-    mut hide_query: Query<&mut Style, With<StartMenuEntityHIDE>>,
-) {
-    // TODO Fix 3d picking and just despawn
-    hide_query.for_each_mut(|mut s| {
-        s.display = Display::None;
-    });
-    dbg!("destroy");
-    query.for_each(|e| commands.entity(e).despawn_recursive());
+    // Clicking rolls a new seed, typing digits edits it
+    let seed_button = commands
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                min_size: Size::new(Val::Px(300.), Val::Px(40.)),
+                margin: Rect::all(Val::Px(8.)),
+                padding: Rect::all(Val::Px(8.)),
+                align_content: AlignContent::Center,
+                align_items: AlignItems::Center,
+                align_self: AlignSelf::Center,
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            color: Color::rgb(0.2, 0.2, 0.2).into(),
+            ..Default::default()
+        })
+        .insert(Name::new("button:seed"))
+        .insert(ButtonAction::RerollSeed)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        format!("Seed: {}", seed.0),
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: 20.0,
+                            color: Color::rgb(0.8, 0.8, 0.8),
+                        },
+                        TextAlignment {
+                            vertical: VerticalAlign::Center,
+                            horizontal: HorizontalAlign::Center,
+                        },
+                    ),
+                    ..Default::default()
+                })
+                .insert(SeedText {});
+        })
+        .id();
+    children.push(seed_button);
+
+    // Recorded as children right away, so that leaving the menu on the frame it is shown still
+    // despawns the buttons along with it
+    commands.entity(container).push_children(&children);
 }
 
 #[allow(clippy::type_complexity)]
-pub fn button_selection(
+fn button_selection(
     mut commands: Commands,
     mut game_state: ResMut<State<GameState>>,
+    mut seed: ResMut<MapSeed>,
+    save_available: Res<SaveAvailable>,
     mut interaction_query: Query<
         (&Interaction, &mut UiColor, &ButtonAction, Entity),
         (Changed<Interaction>, With<Button>),
@@ -152,7 +223,7 @@ pub fn button_selection(
         match *interaction {
             Interaction::Clicked => {
                 *color = Color::rgb(0.3, 0.3, 0.3).into();
-                button_action.run(&mut game_state); // TODO
+                button_action.run(&mut commands, &mut game_state, &mut seed, save_available.0);
             }
             Interaction::Hovered => {
                 // NOTE: We dont need to remove the Animator afterwards
@@ -174,27 +245,124 @@ pub fn button_selection(
     }
 }
 
-#[derive(Component)]
-struct StartMenuEntity {}
+/// Typing digits replaces the shown seed with the one typed, backspace removes its last digit
+fn edit_seed(
+    mut characters: EventReader<ReceivedCharacter>,
+    keys: Res<Input<KeyCode>>,
+    mut seed: ResMut<MapSeed>,
+    // Last seed edited here, any other one was rolled and is cleared by the first digit typed
+    mut typed: Local<Option<u64>>,
+) {
+    for c in characters.iter() {
+        if let Some(digit) = c.char.to_digit(10) {
+            let current = if *typed == Some(seed.0) { seed.0 } else { 0 };
+            if let Some(s) = current
+                .checked_mul(10)
+                .and_then(|s| s.checked_add(digit as u64))
+            {
+                seed.0 = s;
+                *typed = Some(s);
+            }
+        }
+    }
+    if keys.just_pressed(KeyCode::Back) {
+        seed.0 /= 10;
+        *typed = Some(seed.0);
+    }
+}
+
+/// Looked up whenever the menu is shown, a game may have been saved or finished since
+fn check_save(mut commands: Commands) {
+    commands.insert_resource(SaveAvailable(SaveGame::exists()));
+}
+
+/// Greys out Continue when there is no save to continue
+fn update_continue_button(
+    save_available: Res<SaveAvailable>,
+    buttons: Query<(&ButtonAction, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    let text_color = if save_available.0 {
+        Color::rgb(0.8, 0.8, 0.8)
+    } else {
+        Color::rgb(0.4, 0.4, 0.4)
+    };
+    for (action, children) in buttons.iter() {
+        if !matches!(action, ButtonAction::Continue) {
+            continue;
+        }
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                if text.sections[0].style.color != text_color {
+                    text.sections[0].style.color = text_color;
+                }
+            }
+        }
+    }
+}
+
+fn update_seed_text(seed: Res<MapSeed>, mut query: Query<&mut Text, With<SeedText>>) {
+    if !seed.is_changed() {
+        return;
+    }
+    for mut text in query.iter_mut() {
+        text.sections[0].value = format!("Seed: {}", seed.0);
+    }
+}
 
 #[derive(Component)]
-struct StartMenuEntityHIDE {}
+struct SeedText {}
+
+/// Whether there is a saved game to continue
+#[derive(Default)]
+struct SaveAvailable(bool);
 
 #[derive(Component, Clone)]
 pub enum ButtonAction {
     Continue,
     NewGame,
+    /// Plays back the last game played
+    WatchReplay,
+    RerollSeed,
     Quit,
 }
 
 impl ButtonAction {
-    fn run(&self, game_state: &mut ResMut<State<GameState>>) {
+    fn run(
+        &self,
+        commands: &mut Commands,
+        game_state: &mut ResMut<State<GameState>>,
+        seed: &mut MapSeed,
+        save_available: bool,
+    ) {
         match self {
             ButtonAction::Continue => {
-                dbg!("Not implemented");
+                if !save_available {
+                    return;
+                }
+                match SaveGame::load() {
+                    Ok(save) => {
+                        if let Err(e) = game_state.set(GameState::Defense) {
+                            warn!("Could not continue the saved game: {}", e);
+                            return;
+                        }
+                        // The map is generated again from the saved seed
+                        *seed = MapSeed(save.seed);
+                        commands.insert_resource(LoadedSave(save));
+                    }
+                    Err(e) => warn!("Could not load the saved game: {:#}", e),
+                }
             }
             ButtonAction::NewGame => {
-                game_state.set(GameState::Defense).unwrap();
+                if let Err(e) = game_state.set(GameState::Defense) {
+                    warn!("Could not start a new game: {}", e);
+                }
+            }
+            ButtonAction::WatchReplay => {
+                replay::watch_replay(&Replay::last_path(), commands, seed, game_state);
+            }
+            ButtonAction::RerollSeed => {
+                map::roll_seed(commands, seed);
             }
             ButtonAction::Quit => {
                 std::process::exit(0);
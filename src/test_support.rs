@@ -0,0 +1,88 @@
+//! Helpers for the tests: [`headless_app`]s playing a fixed map, and ways to poke at them the
+//! way the player would.
+use crate::{
+    economy::PlayerResources,
+    game_state::GameState,
+    map::{
+        check_placement, Block, BlockClicked, EnemyPath, MapConfig, MapSeed, PathRules, Surface,
+    },
+    sim::headless,
+    tower::{BuildSelection, TowerAssets, TowerCatalogue},
+};
+use bevy::{app::Events, prelude::*};
+
+pub use crate::sim::headless::HeadlessApp;
+
+/// Seed of the map played by [`headless_app`], so that tests always see the same one
+pub const TEST_SEED: u64 = 0;
+
+/// A [`headless::headless_app`] playing the map of [`TEST_SEED`]
+pub fn headless_app() -> App {
+    let mut app = headless::headless_app();
+    app.insert_resource(MapSeed(TEST_SEED));
+    app
+}
+
+/// A [`headless_app`] with a game started, and its towers and waves loaded
+pub fn headless_game() -> App {
+    let mut app = headless_app();
+    app.set_state(GameState::Defense)
+        .wait_until_ready()
+        .unwrap();
+    app
+}
+
+/// What tests do to a [`headless_app`] on top of driving it
+pub trait TestApp {
+    /// Clicks on a block, the way picking reports it in the game
+    fn click_block(&mut self, block: Entity) -> &mut Self;
+
+    /// Number of entities with a `T`
+    fn count<T: Component>(&mut self) -> usize;
+
+    /// A block the selected kind of tower can be built on right now, and its cell. Panics if
+    /// there is none.
+    fn buildable_block(&mut self) -> (Entity, IVec2);
+}
+
+impl TestApp for App {
+    fn click_block(&mut self, block: Entity) -> &mut Self {
+        self.world
+            .get_resource_mut::<Events<BlockClicked>>()
+            .unwrap()
+            .send(BlockClicked { block });
+        self
+    }
+
+    fn count<T: Component>(&mut self) -> usize {
+        self.world
+            .query_filtered::<(), With<T>>()
+            .iter(&self.world)
+            .count()
+    }
+
+    fn buildable_block(&mut self) -> (Entity, IVec2) {
+        let mut blocks = self.world.query::<(Entity, &Block)>();
+        let world = &self.world;
+        let kind = world.get_resource::<BuildSelection>().unwrap().kind;
+        let config = world.get_resource::<MapConfig>().unwrap();
+        let surface = world.get_resource::<Surface>().unwrap();
+        let found = blocks.iter(world).find(|(_, block)| {
+            block.is_free(config, surface)
+                && check_placement(
+                    block,
+                    kind,
+                    config,
+                    surface,
+                    world.get_resource::<PathRules>().unwrap(),
+                    world.get_resource::<EnemyPath>(),
+                    world.get_resource::<TowerAssets>().unwrap(),
+                    world.get_resource::<Assets<TowerCatalogue>>().unwrap(),
+                    world.get_resource::<PlayerResources>().unwrap(),
+                )
+                .is_ok()
+        });
+        let (entity, block) = found.expect("No block to build on");
+        (entity, block.cell(config))
+    }
+}
@@ -1,5 +1,19 @@
-use crate::game_state::GameState;
+use crate::{
+    economy::PlayerResources,
+    enemy::{Enemy, PathFollower},
+    game_state::{GameState, StateScoped},
+    map::MapConfig,
+    projectile::{DamageDealt, EnemyKilled, ProjectileSpec},
+    sim::{
+        CommandSystem, PlayerCommand, PlayerCommands, SimReadiness, SimSystem, SimTime,
+        SimulationApp,
+    },
+};
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+mod catalogue;
+pub use catalogue::{TowerCatalogue, TowerCatalogueLoader, TowerSpec, TowerTier, UpgradePath};
 
 // https://github.com/Leafwing-Studios/leafwing-input-manager/blob/446ac84cfcd2c76ae5607cca1c871681af09a0d9/src/lib.rs#L98
 #[derive(Default)]
@@ -23,11 +37,37 @@ impl TowerPlugin {
 
 impl Plugin for TowerPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<TowerAssets>();
-        if let Some(desired_state) = self.desired_state {
+        app.add_asset::<TowerCatalogue>()
+            .init_asset_loader::<TowerCatalogueLoader>()
+            .init_resource::<TowerAssets>()
+            .init_resource::<BuildSelection>()
+            .init_resource::<SelectedTower>()
+            .add_event::<TowerUpgraded>()
+            .add_event::<TowerSold>();
+        if self.desired_state.is_some() {
             app //.add_system_set(SystemSet::on_enter(desired_state).with_system(setup))
-                .add_system_set(SystemSet::on_update(desired_state).with_system(aim_towers))
-                .add_system_set(SystemSet::on_exit(desired_state).with_system(destroy));
+                .wait_for_asset(TOWER_CATALOGUE)
+                .add_system(report_catalogue)
+                .add_command_system_set(
+                    SystemSet::new()
+                        .label(CommandSystem::Apply)
+                        .with_system(upgrade_towers)
+                        .with_system(sell_towers)
+                        .with_system(change_priorities),
+                )
+                .add_sim_system_set(
+                    SystemSet::new()
+                        .label(SimSystem::Combat)
+                        .after(SimSystem::Movement)
+                        .with_system(acquire_targets.label(TowerSystem::Target))
+                        .with_system(aim_towers.after(TowerSystem::Target)),
+                )
+                .add_sim_system_set(
+                    SystemSet::new()
+                        .label(SimSystem::Stats)
+                        .after(SimSystem::Combat)
+                        .with_system(record_stats),
+                );
         } else {
             panic!("TowerPlugin::run_in_state() must be called with a GameState");
         }
@@ -36,52 +76,320 @@ impl Plugin for TowerPlugin {
 
 #[derive(Default, Clone)]
 pub struct TowerAssets {
-    pub cannon_mesh: Handle<Mesh>,
-    pub body_mesh: Handle<Mesh>,
-    pub material: Handle<StandardMaterial>,
+    pub catalogue: Handle<TowerCatalogue>,
+}
+
+/// Name of the tower catalogue in the [`SimReadiness`]
+const TOWER_CATALOGUE: &str = "tower catalogue";
+
+/// Tells the simulation whether the tower catalogue is loaded
+fn report_catalogue(
+    tower_assets: Res<TowerAssets>,
+    catalogues: Res<Assets<TowerCatalogue>>,
+    mut readiness: ResMut<SimReadiness>,
+) {
+    let loaded = catalogues.get(&tower_assets.catalogue).is_some();
+    readiness.report(TOWER_CATALOGUE, loaded);
+}
+
+impl TowerAssets {
+    /// The spec of a kind of tower, `None` until the catalogue is loaded
+    pub fn spec<'a>(
+        &self,
+        catalogues: &'a Assets<TowerCatalogue>,
+        kind: TowerKind,
+    ) -> Option<&'a TowerSpec> {
+        catalogues.get(&self.catalogue)?.get(kind)
+    }
+}
+
+#[derive(SystemLabel, Clone, Hash, Debug, PartialEq, Eq)]
+pub enum TowerSystem {
+    Target,
+}
+
+/// Kind of tower built by the next click on a block
+#[derive(Default)]
+pub struct BuildSelection {
+    pub kind: TowerKind,
+}
+
+/// Tower whose details are shown, if any
+#[derive(Default)]
+pub struct SelectedTower {
+    pub tower: Option<Entity>,
 }
 
-// TODO: Use GlobalTransform instead
-pub fn spawn_tower(commands: &mut Commands, position: Vec3, tower_assets: &ResMut<TowerAssets>) {
-    let scale = 1.8;
-    let offset = 3.5;
+pub struct TowerUpgraded {
+    pub tower: Entity,
+    pub path: usize,
+    pub tier: usize,
+    pub cost: u32,
+}
+
+/// Sent right before a sold tower is despawned
+pub struct TowerSold {
+    pub tower: Entity,
+    pub kind: TowerKind,
+    pub refund: u32,
+}
+
+/// Share of the gold invested in a tower that selling it gives back
+pub const SELL_REFUND: f32 = 0.7;
+
+/// How much bigger a tower gets with every tier
+const TIER_GROWTH: f32 = 0.12;
+
+/// Height of the tower's parts above the block it stands on
+const PART_OFFSET: f32 = 3.5;
+
+/// Size of the parts of a tower after `tier` upgrades
+fn part_scale(spec: &TowerSpec, tier: usize) -> f32 {
+    spec.scale * (1.0 + TIER_GROWTH * tier as f32)
+}
+
+/// Where the cannon and the body of a tower sit after `tier` upgrades, relative to the tower
+pub fn part_transforms(spec: &TowerSpec, tier: usize) -> (Transform, Transform) {
+    let scale = part_scale(spec, tier);
+    let cannon = Transform::from_translation(Vec3::new(0.0, PART_OFFSET + scale, 0.0))
+        .with_scale(Vec3::splat(scale));
+    let body = Transform::from_translation(Vec3::new(0.0, PART_OFFSET + 1.0, 0.0))
+        .with_scale(Vec3::splat(scale));
+    (cannon, body)
+}
+
+/// Spawns a tower standing on the block at `position`, in world space. Its parts already have
+/// the size its upgrades give them, their meshes are added by the
+/// [`RenderingPlugin`](crate::rendering::RenderingPlugin).
+pub fn spawn_tower(
+    commands: &mut Commands,
+    tower: Tower,
+    position: Vec3,
+    spec: &TowerSpec,
+) -> Entity {
+    let (cannon, body) = part_transforms(spec, tower.tier);
     commands
         .spawn_bundle(TowerBundle {
-            properties: Tower::default(),
+            properties: tower,
             transform: Transform::from_translation(position),
-            ..Default::default()
+            global_transform: GlobalTransform::default(),
         })
+        .insert(StateScoped(GameState::Defense))
+        .insert(Name::new(format!("tower:{}", spec.name)))
         .with_children(|p| {
-            p.spawn_bundle(PbrBundle {
-                mesh: tower_assets.cannon_mesh.clone(),
-                material: tower_assets.material.clone(),
-                transform: Transform::from_translation(Vec3::new(0.0, offset + (1.0 * scale), 0.0))
-                    .with_scale(Vec3::new(scale, scale, scale)),
-                ..Default::default()
-            })
-            .insert(TowerCannon::default());
-            p.spawn_bundle(PbrBundle {
-                mesh: tower_assets.body_mesh.clone(),
-                material: tower_assets.material.clone(),
-                transform: Transform::from_translation(Vec3::new(0.0, offset + 1.0, 0.0))
-                    .with_scale(Vec3::new(scale, scale, scale)),
-                ..Default::default()
-            })
-            .insert(TowerBody::default());
+            p.spawn_bundle((cannon, GlobalTransform::default(), TowerCannon::default()));
+            p.spawn_bundle((body, GlobalTransform::default(), TowerBody::default()));
+        })
+        .id()
+}
+
+fn record_stats(
+    mut damage_events: EventReader<DamageDealt>,
+    mut killed_events: EventReader<EnemyKilled>,
+    mut towers: Query<&mut Tower>,
+) {
+    for DamageDealt { tower, amount, .. } in damage_events.iter() {
+        if let Ok(mut tower) = towers.get_mut(*tower) {
+            tower.damage_dealt += amount;
+        }
+    }
+    for EnemyKilled { tower, .. } in killed_events.iter() {
+        if let Ok(mut tower) = towers.get_mut(*tower) {
+            tower.kills += 1;
+        }
+    }
+}
+
+/// Tower standing on `cell`, which is how [`PlayerCommand`]s refer to towers
+fn tower_on<'a>(
+    cell: (i32, i32),
+    config: &MapConfig,
+    towers: impl IntoIterator<Item = (Entity, &'a Transform)>,
+) -> Option<Entity> {
+    let cell = IVec2::new(cell.0, cell.1);
+    // Towers have no parent, their transform is already in world space
+    towers
+        .into_iter()
+        .find(|(_, transform)| config.cell_at(transform.translation) == cell)
+        .map(|(entity, _)| entity)
+}
+
+/// Buys the next tier of a path for the towers of [`PlayerCommand::Upgrade`]s
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn upgrade_towers(
+    commands: Res<PlayerCommands>,
+    config: Res<MapConfig>,
+    mut upgraded: EventWriter<TowerUpgraded>,
+    mut resources: ResMut<PlayerResources>,
+    tower_assets: Res<TowerAssets>,
+    catalogues: Res<Assets<TowerCatalogue>>,
+    mut towers: Query<(Entity, &mut Tower, &Children)>,
+    positions: Query<(Entity, &Transform), With<Tower>>,
+    mut parts: Query<(&mut Transform, Option<&TowerCannon>), Without<Tower>>,
+) {
+    for command in commands.iter() {
+        let (cell, path) = match command {
+            PlayerCommand::Upgrade { cell, path } => (*cell, path),
+            _ => continue,
+        };
+        let found = tower_on(cell, &config, positions.iter());
+        let (entity, mut tower, children) = match found.and_then(|e| towers.get_mut(e).ok()) {
+            Some(tower) => tower,
+            None => continue,
+        };
+        let spec = match tower_assets.spec(&catalogues, tower.kind) {
+            Some(spec) => spec,
+            None => continue,
+        };
+        let tier = match tower.next_tier(spec, *path) {
+            Some(tier) => tier,
+            None => {
+                info!("{} can not be upgraded along path {}", spec.name, path);
+                continue;
+            }
+        };
+        if !resources.spend(tier.cost) {
+            info!("Not enough gold for the upgrade ({} needed)", tier.cost);
+            continue;
+        }
+        tower.upgrade(*path, tier);
+        upgraded.send(TowerUpgraded {
+            tower: entity,
+            path: *path,
+            tier: tower.tier,
+            cost: tier.cost,
         });
+
+        // The cannon keeps aiming where it was
+        let (cannon_transform, body_transform) = part_transforms(spec, tower.tier);
+        for child in children.iter() {
+            if let Ok((mut transform, cannon)) = parts.get_mut(*child) {
+                let part = if cannon.is_some() {
+                    cannon_transform
+                } else {
+                    body_transform
+                };
+                transform.translation = part.translation;
+                transform.scale = part.scale;
+            }
+        }
+    }
+}
+
+/// Despawns the towers of [`PlayerCommand::Sell`]s and refunds part of the gold invested in them
+fn sell_towers(
+    mut commands: Commands,
+    player_commands: Res<PlayerCommands>,
+    config: Res<MapConfig>,
+    mut sold: EventWriter<TowerSold>,
+    towers: Query<(Entity, &Transform, &Tower)>,
+) {
+    let mut despawned = Vec::new();
+    for command in player_commands.iter() {
+        let cell = match command {
+            PlayerCommand::Sell { cell } => *cell,
+            _ => continue,
+        };
+        let entity = match tower_on(cell, &config, towers.iter().map(|(e, t, _)| (e, t))) {
+            Some(entity) if !despawned.contains(&entity) => entity,
+            _ => continue,
+        };
+        if let Ok((_, _, tower)) = towers.get(entity) {
+            sold.send(TowerSold {
+                tower: entity,
+                kind: tower.kind,
+                refund: tower.sell_value(),
+            });
+            commands.entity(entity).despawn_recursive();
+            despawned.push(entity);
+        }
+    }
+}
+
+fn change_priorities(
+    commands: Res<PlayerCommands>,
+    config: Res<MapConfig>,
+    mut towers: Query<(Entity, &mut Tower)>,
+    positions: Query<(Entity, &Transform), With<Tower>>,
+) {
+    for command in commands.iter() {
+        if let PlayerCommand::SetPriority { cell, priority } = command {
+            let found = tower_on(*cell, &config, positions.iter());
+            if let Some((_, mut tower)) = found.and_then(|e| towers.get_mut(e).ok()) {
+                tower.priority = *priority;
+            }
+        }
+    }
 }
 
-fn aim_towers(time: Res<Time>, mut towers: Query<&mut Transform, With<TowerCannon>>) {
-    for mut transform in (&mut towers).iter_mut() {
-        transform.rotate(Quat::from_rotation_y(time.delta_seconds()));
+/// Picks, for every tower, the enemy in range that best matches its [`TargetPriority`]
+fn acquire_targets(
+    mut towers: Query<(&GlobalTransform, &mut Tower)>,
+    enemies: Query<(Entity, &GlobalTransform, &Enemy, &PathFollower)>,
+) {
+    for (tower_transform, mut tower) in towers.iter_mut() {
+        let origin = tower_transform.translation;
+        let in_range = enemies
+            .iter()
+            .filter(|(_, _, _, follower)| !follower.reached_goal())
+            .filter_map(|(entity, transform, enemy, follower)| {
+                let distance = horizontal_distance(origin, transform.translation);
+                (distance <= tower.range).then_some((entity, distance, enemy, follower))
+            });
+
+        let priority = tower.priority;
+        let target = match priority {
+            TargetPriority::First => in_range
+                .max_by(|a, b| a.3.travelled.total_cmp(&b.3.travelled))
+                .map(|t| t.0),
+            TargetPriority::Last => in_range
+                .min_by(|a, b| a.3.travelled.total_cmp(&b.3.travelled))
+                .map(|t| t.0),
+            TargetPriority::Strongest => in_range
+                .max_by(|a, b| a.2.health.total_cmp(&b.2.health))
+                .map(|t| t.0),
+            TargetPriority::Closest => in_range.min_by(|a, b| a.1.total_cmp(&b.1)).map(|t| t.0),
+        };
+        if tower.target != target {
+            tower.target = target;
+        }
+    }
+}
+
+/// Turns the cannon of every tower towards its target
+fn aim_towers(
+    time: Res<SimTime>,
+    towers: Query<(&Tower, &GlobalTransform, &Children)>,
+    mut cannons: Query<(&mut Transform, &GlobalTransform), With<TowerCannon>>,
+    targets: Query<&GlobalTransform, With<Enemy>>,
+) {
+    for (tower, tower_transform, children) in towers.iter() {
+        let target = match tower.target.and_then(|t| targets.get(t).ok()) {
+            Some(target) => target.translation,
+            None => continue,
+        };
+        for child in children.iter() {
+            if let Ok((mut transform, cannon_transform)) = cannons.get_mut(*child) {
+                let direction = target - cannon_transform.translation;
+                if direction.x == 0.0 && direction.z == 0.0 {
+                    continue;
+                }
+                // Yaw that points the cannon's forward (-Z) at the target, in world space, then
+                // brought back into the tower's space since the cannon is its child
+                let world = Quat::from_rotation_y(f32::atan2(-direction.x, -direction.z));
+                let local = tower_transform.rotation.inverse() * world;
+                let t = (tower.turn_speed * time.delta_seconds()).min(1.0);
+                transform.rotation = transform.rotation.slerp(local, t);
+            }
+        }
     }
 }
 
-fn destroy(mut commands: Commands, query: Query<Entity, With<Tower>>) {
-    commands.entity(query.single()).despawn_recursive();
+fn horizontal_distance(a: Vec3, b: Vec3) -> f32 {
+    Vec2::new(a.x - b.x, a.z - b.z).length()
 }
 
-#[derive(Bundle, Default)]
+#[derive(Bundle)]
 pub struct TowerBundle {
     pub transform: Transform,
     pub global_transform: GlobalTransform,
@@ -94,13 +402,128 @@ pub struct TowerBody {}
 #[derive(Component, Default)]
 pub struct TowerCannon {}
 
-#[derive(Component, Default)]
+#[derive(Component)]
 pub struct Tower {
-    kind: TowerKind,
+    pub kind: TowerKind,
+    /// Horizontal distance, in world units, at which enemies can be targeted
+    pub range: f32,
+    pub priority: TargetPriority,
+    pub target: Option<Entity>,
+    /// How fast the cannon turns towards its target, higher is faster
+    pub turn_speed: f32,
+    /// Damage dealt by each projectile
+    pub damage: f32,
+    /// Shots per second
+    pub fire_rate: f32,
+    /// Seconds left before the tower can fire again
+    pub reload: f32,
+    pub projectile: ProjectileSpec,
+    /// Upgrade path chosen with the first upgrade
+    pub upgrade_path: Option<usize>,
+    /// Number of upgrades bought
+    pub tier: usize,
+    /// Gold spent on building and upgrading the tower
+    pub invested: u32,
+    pub kills: u32,
+    pub damage_dealt: f32,
 }
 
+impl Tower {
+    pub fn from_spec(kind: TowerKind, spec: &TowerSpec) -> Self {
+        Self {
+            kind,
+            range: spec.range,
+            priority: TargetPriority::default(),
+            target: None,
+            turn_speed: spec.turn_speed,
+            damage: spec.damage,
+            fire_rate: spec.fire_rate,
+            reload: 0.0,
+            projectile: spec.projectile.clone(),
+            upgrade_path: None,
+            tier: 0,
+            invested: spec.cost,
+            kills: 0,
+            damage_dealt: 0.0,
+        }
+    }
+
+    /// Next upgrade along `path`, `None` once the path is maxed out or if the tower already
+    /// went down another one
+    pub fn next_tier<'a>(&self, spec: &'a TowerSpec, path: usize) -> Option<&'a TowerTier> {
+        if matches!(self.upgrade_path, Some(chosen) if chosen != path) {
+            return None;
+        }
+        spec.tier(path, self.tier + 1)
+    }
+
+    pub fn sell_value(&self) -> u32 {
+        (self.invested as f32 * SELL_REFUND).round() as u32
+    }
+
+    /// Buys `tiers` upgrades along `path` for free, stopping early if the path is shorter. Used
+    /// to bring back a tower from a saved game.
+    pub fn restore_upgrades(&mut self, spec: &TowerSpec, path: usize, tiers: usize) {
+        for _ in 0..tiers {
+            match self.next_tier(spec, path) {
+                Some(tier) => self.upgrade(path, tier),
+                None => break,
+            }
+        }
+    }
+
+    fn upgrade(&mut self, path: usize, tier: &TowerTier) {
+        self.upgrade_path = Some(path);
+        self.tier += 1;
+        self.invested += tier.cost;
+        self.range = tier.range;
+        self.damage = tier.damage;
+        self.fire_rate = tier.fire_rate;
+        if let Some(projectile) = &tier.projectile {
+            self.projectile = projectile.clone();
+        }
+    }
+}
+
+/// Which enemy in range a tower shoots at
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TargetPriority {
+    /// Furthest along the path
+    #[default]
+    First,
+    /// Least far along the path
+    Last,
+    /// Most health left
+    Strongest,
+    Closest,
+}
+
+impl TargetPriority {
+    pub const ALL: [TargetPriority; 4] = [
+        TargetPriority::First,
+        TargetPriority::Last,
+        TargetPriority::Strongest,
+        TargetPriority::Closest,
+    ];
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum TowerKind {
     Cannon,
+    Archer,
+    Sniper,
+    Frost,
+    Mortar,
+}
+
+impl TowerKind {
+    pub const ALL: [TowerKind; 5] = [
+        TowerKind::Cannon,
+        TowerKind::Archer,
+        TowerKind::Sniper,
+        TowerKind::Frost,
+        TowerKind::Mortar,
+    ];
 }
 
 impl Default for TowerKind {
@@ -0,0 +1,92 @@
+use super::TowerKind;
+use crate::projectile::ProjectileSpec;
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Stats, costs and looks of every kind of tower
+#[derive(Deserialize, TypeUuid, Clone, Debug)]
+#[uuid = "0b7d3a52-8f0e-4d2a-b6b9-3c1e5f7a9d24"]
+pub struct TowerCatalogue {
+    pub towers: HashMap<TowerKind, TowerSpec>,
+}
+
+impl TowerCatalogue {
+    pub fn get(&self, kind: TowerKind) -> Option<&TowerSpec> {
+        self.towers.get(&kind)
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct TowerSpec {
+    pub name: String,
+    /// Asset paths of the meshes
+    pub cannon_mesh: String,
+    pub body_mesh: String,
+    pub color: (f32, f32, f32),
+    pub scale: f32,
+    /// Gold needed to build the tower
+    pub cost: u32,
+    pub range: f32,
+    pub turn_speed: f32,
+    pub damage: f32,
+    pub fire_rate: f32,
+    pub projectile: ProjectileSpec,
+    /// Branching upgrades. A tower picks one path with its first upgrade and sticks to it.
+    #[serde(default)]
+    pub upgrades: Vec<UpgradePath>,
+}
+
+impl TowerSpec {
+    /// Tier `tier` of the upgrade path `path`, counting from 1 for the first upgrade
+    pub fn tier(&self, path: usize, tier: usize) -> Option<&TowerTier> {
+        self.upgrades.get(path)?.tiers.get(tier.checked_sub(1)?)
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct UpgradePath {
+    pub name: String,
+    /// Bought in order
+    pub tiers: Vec<TowerTier>,
+}
+
+/// Stats of a tower once upgraded
+#[derive(Deserialize, Clone, Debug)]
+pub struct TowerTier {
+    pub cost: u32,
+    pub range: f32,
+    pub damage: f32,
+    pub fire_rate: f32,
+    /// Replaces the tower's projectile, the previous one is kept otherwise
+    #[serde(default)]
+    pub projectile: Option<ProjectileSpec>,
+    /// Replaces the tower's color, the previous one is kept otherwise
+    #[serde(default)]
+    pub color: Option<(f32, f32, f32)>,
+}
+
+#[derive(Default)]
+pub struct TowerCatalogueLoader;
+
+impl AssetLoader for TowerCatalogueLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let catalogue: TowerCatalogue = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(catalogue));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["towers.ron"]
+    }
+}
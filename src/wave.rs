@@ -0,0 +1,344 @@
+use crate::{
+    enemy::{Enemy, EnemyKind, EnemySystem, SpawnEnemy},
+    game_state::{GameState, StateScoped},
+    sim::{
+        CommandSystem, PlayerCommand, PlayerCommands, SimReadiness, SimSystem, SimTime,
+        SimulationApp,
+    },
+};
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use leafwing_input_manager::{
+    plugin::InputManagerPlugin,
+    prelude::{ActionState, InputMap},
+    Actionlike, InputManagerBundle,
+};
+use serde::Deserialize;
+
+// https://github.com/Leafwing-Studios/leafwing-input-manager/blob/446ac84cfcd2c76ae5607cca1c871681af09a0d9/src/lib.rs#L98
+#[derive(Default)]
+pub struct WavePlugin {
+    desired_state: Option<GameState>,
+}
+
+impl WavePlugin {
+    pub fn new() -> Self {
+        Self {
+            desired_state: None,
+        }
+    }
+
+    pub fn run_in_state(state: GameState) -> Self {
+        Self {
+            desired_state: Some(state),
+        }
+    }
+}
+
+impl Plugin for WavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<WaveSchedule>()
+            .init_asset_loader::<WaveScheduleLoader>()
+            .init_resource::<WaveAssets>()
+            .init_resource::<WaveState>()
+            .add_event::<WaveStarted>()
+            .add_event::<WaveCleared>();
+        if let Some(desired_state) = self.desired_state {
+            let p = InputManagerPlugin::<WaveAction, GameState>::run_in_state(desired_state);
+            app.add_plugin(p)
+                .wait_for_asset(WAVE_SCHEDULE)
+                .add_system(report_schedule)
+                .add_system_set(SystemSet::on_enter(desired_state).with_system(setup))
+                .add_system_set(
+                    SystemSet::on_update(desired_state)
+                        .with_system(call_next_wave_input)
+                        .with_system(end_game),
+                )
+                .add_command_system_set(
+                    SystemSet::new().with_system(queue_wave_calls.label(CommandSystem::Apply)),
+                )
+                .add_sim_system_set(
+                    SystemSet::new()
+                        .label(SimSystem::Waves)
+                        .after(SimSystem::Propagate)
+                        .with_system(run_waves.before(EnemySystem::Spawn)),
+                );
+        } else {
+            panic!("WavePlugin::run_in_state() must be called with a GameState");
+        }
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct WaveAssets {
+    pub schedule: Handle<WaveSchedule>,
+}
+
+/// Name of the wave schedule in the [`SimReadiness`]
+const WAVE_SCHEDULE: &str = "wave schedule";
+
+/// Tells the simulation whether the wave schedule is loaded
+fn report_schedule(
+    wave_assets: Res<WaveAssets>,
+    schedules: Res<Assets<WaveSchedule>>,
+    mut readiness: ResMut<SimReadiness>,
+) {
+    let loaded = schedules.get(&wave_assets.schedule).is_some();
+    readiness.report(WAVE_SCHEDULE, loaded);
+}
+
+/// Every wave of a game, in order
+#[derive(Deserialize, TypeUuid, Clone, Debug)]
+#[uuid = "6f1c3f5e-2b8e-4c47-9a53-0d6c2b7e9a41"]
+pub struct WaveSchedule {
+    pub waves: Vec<Wave>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Wave {
+    /// Break before the wave starts, counted from the end of the previous wave's spawning
+    pub delay: f32,
+    /// Groups spawn in parallel, each one on its own clock
+    pub groups: Vec<WaveGroup>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct WaveGroup {
+    pub kind: EnemyKind,
+    pub count: u32,
+    /// Time between two enemies of the group
+    pub interval: f32,
+    /// Time between the start of the wave and the first enemy of the group
+    #[serde(default)]
+    pub delay: f32,
+}
+
+#[derive(Default)]
+pub struct WaveScheduleLoader;
+
+impl AssetLoader for WaveScheduleLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let schedule: WaveSchedule = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(schedule));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["waves.ron"]
+    }
+}
+
+pub struct WaveStarted {
+    pub index: usize,
+}
+
+/// Sent once every enemy of a wave has been spawned and is gone
+pub struct WaveCleared {
+    pub index: usize,
+}
+
+/// Progress through the [`WaveSchedule`]
+#[derive(Default)]
+pub struct WaveState {
+    /// Index of the next wave to start
+    pub next: usize,
+    /// Total number of waves in the schedule, 0 until it is loaded
+    pub total: usize,
+    /// Time left before the next wave starts. `None` while a wave is still spawning or after
+    /// the last one.
+    pub countdown: Option<Timer>,
+    /// Waves started but not cleared yet
+    pub active: Vec<usize>,
+    /// The next wave was called early, it starts with the next step of the simulation
+    called: bool,
+    spawners: Vec<GroupSpawner>,
+    loaded: bool,
+}
+
+impl WaveState {
+    pub fn is_finished(&self) -> bool {
+        self.loaded && self.next >= self.total && self.active.is_empty()
+    }
+
+    /// Forgets every started wave and counts down to wave `next` instead, as if every wave before
+    /// it had been cleared
+    pub fn resume_at(&mut self, next: usize) {
+        *self = WaveState {
+            next,
+            ..Default::default()
+        };
+    }
+
+    fn start_wave(&mut self, index: usize, wave: &Wave) {
+        self.spawners.extend(
+            wave.groups
+                .iter()
+                .filter(|group| group.count > 0)
+                .map(|group| GroupSpawner {
+                    wave: index,
+                    kind: group.kind,
+                    remaining: group.count,
+                    timer: Timer::from_seconds(group.delay, false),
+                    interval: group.interval,
+                }),
+        );
+        self.active.push(index);
+        self.next = index + 1;
+        self.countdown = None;
+    }
+}
+
+struct GroupSpawner {
+    wave: usize,
+    kind: EnemyKind,
+    remaining: u32,
+    /// Time left until the next enemy
+    timer: Timer,
+    interval: f32,
+}
+
+fn setup(mut commands: Commands, mut wave_state: ResMut<WaveState>) {
+    *wave_state = WaveState::default();
+    commands
+        .spawn_bundle(InputManagerBundle {
+            input_map: default_input_map(),
+            ..Default::default()
+        })
+        .insert(StateScoped(GameState::Defense));
+}
+
+/// Calls reach the next step through the [`WaveState`], the command pass may run without a step
+/// following it when the game is stopped
+fn queue_wave_calls(commands: Res<PlayerCommands>, mut wave_state: ResMut<WaveState>) {
+    if commands.iter().any(|c| *c == PlayerCommand::CallNextWave) {
+        wave_state.called = true;
+    }
+}
+
+/// Ends the game once every wave is cleared, asking for the transition a single time
+fn end_game(
+    wave_state: Res<WaveState>,
+    mut game_state: ResMut<State<GameState>>,
+    mut ending: Local<bool>,
+) {
+    if !wave_state.is_finished() {
+        *ending = false;
+        return;
+    }
+    if *ending || game_state.current() != &GameState::Defense {
+        return;
+    }
+    *ending = true;
+    if let Err(e) = game_state.set(GameState::End) {
+        warn!("Could not end the game: {}", e);
+    }
+}
+
+/// Starts the next wave right away instead of waiting for the countdown
+fn call_next_wave_input(
+    actions: Query<&ActionState<WaveAction>>,
+    mut commands: ResMut<PlayerCommands>,
+) {
+    if actions
+        .iter()
+        .any(|actions| actions.just_pressed(&WaveAction::CallNextWave))
+    {
+        commands.push(PlayerCommand::CallNextWave);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_waves(
+    time: Res<SimTime>,
+    wave_assets: Res<WaveAssets>,
+    schedules: Res<Assets<WaveSchedule>>,
+    mut wave_state: ResMut<WaveState>,
+    mut spawn_events: EventWriter<SpawnEnemy>,
+    mut started: EventWriter<WaveStarted>,
+    mut cleared: EventWriter<WaveCleared>,
+    enemies: Query<&Enemy>,
+) {
+    let schedule = match schedules.get(&wave_assets.schedule) {
+        Some(schedule) => schedule,
+        None => return,
+    };
+    let state = &mut *wave_state;
+    if !state.loaded {
+        state.loaded = true;
+        state.total = schedule.waves.len();
+        state.countdown = schedule
+            .waves
+            .get(state.next)
+            .map(|wave| Timer::from_seconds(wave.delay, false));
+    }
+
+    // Start the next wave when its countdown runs out or when it is called early
+    let called = std::mem::take(&mut state.called);
+    let countdown_done = match &mut state.countdown {
+        Some(countdown) => countdown.tick(time.delta()).finished(),
+        None => false,
+    };
+    if (called || countdown_done) && state.next < schedule.waves.len() {
+        let index = state.next;
+        state.start_wave(index, &schedule.waves[index]);
+        started.send(WaveStarted { index });
+    }
+
+    // Spawn enemies
+    let mut spawned = Vec::new();
+    for spawner in state.spawners.iter_mut() {
+        if spawner.timer.tick(time.delta()).finished() {
+            spawn_events.send(SpawnEnemy {
+                kind: spawner.kind,
+                wave: Some(spawner.wave),
+            });
+            spawned.push(spawner.wave);
+            spawner.remaining -= 1;
+            spawner.timer = Timer::from_seconds(spawner.interval, false);
+        }
+    }
+    state.spawners.retain(|spawner| spawner.remaining > 0);
+
+    // Once every started wave is done spawning, count down to the next one
+    if state.spawners.is_empty() && state.countdown.is_none() {
+        state.countdown = schedule
+            .waves
+            .get(state.next)
+            .map(|wave| Timer::from_seconds(wave.delay, false));
+    }
+
+    // A wave is cleared once it is done spawning and none of its enemies are left. Enemies
+    // requested this step are only spawned at the end of it, so their wave is kept around.
+    let spawners = &state.spawners;
+    let (done, still_active): (Vec<usize>, Vec<usize>) =
+        state.active.iter().copied().partition(|index| {
+            !spawned.contains(index)
+                && !spawners.iter().any(|s| s.wave == *index)
+                && !enemies.iter().any(|e| e.wave == Some(*index))
+        });
+    state.active = still_active;
+    for index in done {
+        cleared.send(WaveCleared { index });
+    }
+}
+
+fn default_input_map() -> InputMap<WaveAction> {
+    let mut input_map: InputMap<WaveAction> = InputMap::default();
+    input_map.insert(WaveAction::CallNextWave, KeyCode::N);
+    input_map
+}
+
+#[derive(Actionlike, Debug, Clone, Hash, PartialEq, Eq, Copy)]
+pub enum WaveAction {
+    CallNextWave,
+}
@@ -0,0 +1,58 @@
+use bevy::prelude::*;
+use yatd_lib::{
+    economy::PlayerResources,
+    map::{Chunk, ChunkMap, EXPANSION_COST},
+    sim::PlayerCommand,
+    test_support::{headless_game, HeadlessApp, TestApp},
+};
+
+fn gold(app: &App) -> u32 {
+    app.world.get_resource::<PlayerResources>().unwrap().gold
+}
+
+fn next_expansion(app: &App) -> IVec2 {
+    app.world
+        .get_resource::<ChunkMap>()
+        .unwrap()
+        .next_expansion()
+        .expect("The first chunk has free neighbours")
+}
+
+#[test]
+fn buying_a_chunk_spends_gold_and_spawns_it() {
+    let mut app = headless_game();
+    let coord = next_expansion(&app);
+    let gold_before = gold(&app);
+
+    app.command(PlayerCommand::Expand {
+        chunk: (coord.x, coord.y),
+    })
+    .advance_frames(1);
+
+    assert_eq!(app.count::<Chunk>(), 2);
+    assert_eq!(gold(&app), gold_before - EXPANSION_COST);
+    let chunk_map = app.world.get_resource::<ChunkMap>().unwrap();
+    assert!(chunk_map.get(coord).is_some());
+    assert_eq!(chunk_map.expansion_cost(), 2 * EXPANSION_COST);
+}
+
+#[test]
+fn chunks_that_are_not_next_to_the_map_or_too_dear_are_not_bought() {
+    let mut app = headless_game();
+    app.command(PlayerCommand::Expand { chunk: (5, 5) })
+        .advance_frames(1);
+    assert_eq!(app.count::<Chunk>(), 1);
+
+    app.world
+        .get_resource_mut::<PlayerResources>()
+        .unwrap()
+        .gold = EXPANSION_COST - 1;
+    let coord = next_expansion(&app);
+    app.command(PlayerCommand::Expand {
+        chunk: (coord.x, coord.y),
+    })
+    .advance_frames(1);
+
+    assert_eq!(app.count::<Chunk>(), 1);
+    assert_eq!(gold(&app), EXPANSION_COST - 1);
+}
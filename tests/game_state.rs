@@ -0,0 +1,81 @@
+use yatd_lib::{
+    economy::PlayerResources,
+    game_state::GameState,
+    map::{Block, Chunk, EnemyPath},
+    test_support::{headless_app, headless_game, HeadlessApp, TestApp},
+};
+
+/// Steps after which a game with no towers must have been lost, an hour of game time
+const MAX_STEPS: u64 = 60 * 60 * 60;
+
+#[test]
+fn the_game_starts_in_the_start_menu() {
+    let mut app = headless_app();
+    app.advance_frames(1);
+
+    assert_eq!(app.state(), GameState::StartMenu);
+    assert_eq!(app.count::<Chunk>(), 0);
+}
+
+#[test]
+fn entering_defense_spawns_the_map() {
+    let mut app = headless_app();
+    app.advance_frames(1).set_state(GameState::Defense);
+
+    assert_eq!(app.state(), GameState::Defense);
+    assert_eq!(app.count::<Chunk>(), 1);
+    assert!(app.count::<Block>() > 0);
+    assert!(app.world.contains_resource::<EnemyPath>());
+}
+
+#[test]
+fn losing_every_life_ends_the_game() {
+    let mut app = headless_game();
+    app.world
+        .get_resource_mut::<PlayerResources>()
+        .unwrap()
+        .lives = 1;
+
+    let mut steps = 0;
+    while app.world.get_resource::<PlayerResources>().unwrap().lives > 0 {
+        assert!(steps < MAX_STEPS, "No enemy reached the goal");
+        app.step_simulation(1);
+        steps += 1;
+    }
+    // The transition happens with the next frame
+    assert_eq!(app.state(), GameState::Defense);
+    app.advance_frames(1);
+
+    assert_eq!(app.state(), GameState::End);
+}
+
+#[test]
+fn pausing_keeps_the_game_around() {
+    let mut app = headless_game();
+    let blocks = app.count::<Block>();
+
+    app.push_state(GameState::Paused);
+    assert_eq!(app.state(), GameState::Paused);
+    assert_eq!(app.count::<Block>(), blocks);
+
+    app.pop_state();
+    assert_eq!(app.state(), GameState::Defense);
+    assert_eq!(app.count::<Block>(), blocks);
+}
+
+#[test]
+fn a_new_game_starts_after_the_end() {
+    let mut app = headless_game();
+    app.world
+        .get_resource_mut::<PlayerResources>()
+        .unwrap()
+        .gold = 0;
+    app.set_state(GameState::End).set_state(GameState::Defense);
+
+    assert_eq!(app.state(), GameState::Defense);
+    assert_eq!(app.count::<Chunk>(), 1);
+    assert_eq!(
+        app.world.get_resource::<PlayerResources>().unwrap().gold,
+        PlayerResources::default().gold
+    );
+}
@@ -0,0 +1,100 @@
+use bevy::prelude::*;
+use yatd_lib::{
+    game_state::GameState,
+    map::{Block, BlockKind, Chunk, MapConfig, MapError, MapSeed, RolledSeed},
+    test_support::{headless_app, HeadlessApp, TestApp, TEST_SEED},
+};
+
+/// Cell, height and kind of every spawned block, in a stable order
+fn spawned_blocks(app: &mut App) -> Vec<((i32, i32, i32), BlockKind)> {
+    let config = app.world.get_resource::<MapConfig>().unwrap().clone();
+    let mut blocks = app.world.query::<(&Block, &Transform)>();
+    let mut found: Vec<_> = blocks
+        .iter(&app.world)
+        .map(|(block, transform)| {
+            let cell = block.cell(&config);
+            let height = (transform.translation.y / config.block_size).round() as i32;
+            ((cell.x, cell.y, height), block.kind())
+        })
+        .collect();
+    found.sort_by_key(|(position, _)| *position);
+    found
+}
+
+/// Blocks of the map of `seed`, in a new app
+fn spawn_map(seed: u64) -> Vec<((i32, i32, i32), BlockKind)> {
+    let mut app = headless_app();
+    app.insert_resource(MapSeed(seed))
+        .set_state(GameState::Defense);
+    let blocks = spawned_blocks(&mut app);
+    assert!(!blocks.is_empty(), "No map was spawned for seed {}", seed);
+    blocks
+}
+
+#[test]
+fn the_same_seed_spawns_the_same_map() {
+    assert_eq!(spawn_map(TEST_SEED), spawn_map(TEST_SEED));
+}
+
+#[test]
+fn another_seed_spawns_another_map() {
+    assert_ne!(spawn_map(TEST_SEED), spawn_map(TEST_SEED + 1));
+}
+
+#[test]
+fn a_new_game_on_the_same_seed_spawns_the_same_map() {
+    let mut app = headless_app();
+    app.set_state(GameState::Defense);
+    let blocks = spawned_blocks(&mut app);
+
+    app.set_state(GameState::End).set_state(GameState::Defense);
+    assert_eq!(
+        app.world.get_resource::<MapSeed>(),
+        Some(&MapSeed(TEST_SEED))
+    );
+    assert_eq!(spawned_blocks(&mut app), blocks);
+}
+
+#[test]
+fn a_map_without_an_enemy_path_goes_back_to_the_start_menu() {
+    let mut app = headless_app();
+    // The goal is outside the map, no path can reach it
+    app.world
+        .get_resource_mut::<MapConfig>()
+        .unwrap()
+        .path_endpoints = Some((IVec2::ZERO, IVec2::new(100, 100)));
+    app.set_state(GameState::Defense).advance_frames(1);
+
+    assert_eq!(app.state(), GameState::StartMenu);
+    assert_eq!(
+        app.world.get_resource::<MapError>(),
+        Some(&MapError { seed: TEST_SEED })
+    );
+    assert_eq!(
+        app.world.get_resource::<MapSeed>(),
+        Some(&MapSeed(TEST_SEED))
+    );
+    assert_eq!(app.count::<Chunk>(), 0);
+    assert_eq!(app.count::<Block>(), 0);
+}
+
+#[test]
+fn a_rolled_seed_without_an_enemy_path_is_rerolled() {
+    let mut app = headless_app();
+    app.insert_resource(RolledSeed(TEST_SEED));
+    // No seed has a path, every one of them is tried before giving up
+    app.world
+        .get_resource_mut::<MapConfig>()
+        .unwrap()
+        .path_endpoints = Some((IVec2::ZERO, IVec2::new(100, 100)));
+    app.set_state(GameState::Defense).advance_frames(1);
+
+    assert_eq!(app.state(), GameState::StartMenu);
+    let seed = app.world.get_resource::<MapSeed>().unwrap().0;
+    assert!(seed > TEST_SEED);
+    assert_eq!(app.world.get_resource::<RolledSeed>().unwrap().0, seed);
+    assert_eq!(
+        app.world.get_resource::<MapError>(),
+        Some(&MapError { seed })
+    );
+}
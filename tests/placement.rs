@@ -0,0 +1,134 @@
+use bevy::prelude::*;
+use yatd_lib::{
+    economy::PlayerResources,
+    map::{Block, MapConfig, PlacementPreview, Surface},
+    sim::PlayerCommand,
+    test_support::{headless_game, HeadlessApp, TestApp},
+    tower::{Tower, TowerBody, TowerCannon, TowerKind, SELL_REFUND},
+};
+
+fn gold(app: &App) -> u32 {
+    app.world.get_resource::<PlayerResources>().unwrap().gold
+}
+
+fn cell_of(app: &App, tower: &Transform) -> IVec2 {
+    app.world
+        .get_resource::<MapConfig>()
+        .unwrap()
+        .cell_at(tower.translation)
+}
+
+#[test]
+fn clicking_a_block_twice_builds_a_tower_on_it() {
+    let mut app = headless_game();
+    let (block, cell) = app.buildable_block();
+    let gold_before = gold(&app);
+
+    app.click_block(block).advance_frames(1);
+    assert_eq!(app.count::<Tower>(), 0);
+    // The command is carried out by the command pass of the next frame
+    app.click_block(block).advance_frames(2);
+
+    let mut towers = app.world.query_filtered::<&Transform, With<Tower>>();
+    let positions: Vec<Transform> = towers.iter(&app.world).copied().collect();
+    assert_eq!(positions.len(), 1);
+    assert_eq!(cell_of(&app, &positions[0]), cell);
+    assert_eq!(app.count::<TowerCannon>(), 1);
+    assert_eq!(app.count::<TowerBody>(), 1);
+    assert!(app.world.get::<Block>(block).unwrap().has_tower());
+    assert!(gold(&app) < gold_before);
+    let surface = app.world.get_resource::<Surface>().unwrap();
+    assert!(surface.get(cell).unwrap().blocked);
+}
+
+#[test]
+fn a_single_click_only_previews_the_tower() {
+    let mut app = headless_game();
+    let (block, _) = app.buildable_block();
+
+    app.click_block(block).advance_frames(2);
+
+    assert_eq!(app.count::<Tower>(), 0);
+    let preview = app.world.get_resource::<PlacementPreview>().unwrap();
+    assert_eq!(preview.pending, Some(block));
+}
+
+#[test]
+fn blocks_under_the_top_of_their_column_can_not_be_picked() {
+    let mut app = headless_game();
+    let mut blocks = app.world.query::<(Entity, &Block)>();
+    let config = app.world.get_resource::<MapConfig>().unwrap();
+    let surface = app.world.get_resource::<Surface>().unwrap();
+    let buried = blocks
+        .iter(&app.world)
+        .find(|(_, block)| !block.is_top(config, surface))
+        .map(|(entity, _)| entity)
+        .expect("The test map has a block under the top of its column");
+
+    app.click_block(buried).advance_frames(1);
+    app.click_block(buried).advance_frames(2);
+
+    assert_eq!(app.count::<Tower>(), 0);
+    let preview = app.world.get_resource::<PlacementPreview>().unwrap();
+    assert_eq!(preview.pending, None);
+}
+
+#[test]
+fn a_cell_only_takes_a_single_tower() {
+    let mut app = headless_game();
+    let (_, cell) = app.buildable_block();
+    let build = PlayerCommand::Build {
+        kind: TowerKind::Cannon,
+        cell: (cell.x, cell.y),
+    };
+
+    app.command(build.clone()).advance_frames(1);
+    let gold_after_first = gold(&app);
+    app.command(build).advance_frames(1);
+
+    assert_eq!(app.count::<Tower>(), 1);
+    assert_eq!(gold(&app), gold_after_first);
+}
+
+#[test]
+fn towers_are_not_built_without_the_gold_for_them() {
+    let mut app = headless_game();
+    let (block, cell) = app.buildable_block();
+    app.world
+        .get_resource_mut::<PlayerResources>()
+        .unwrap()
+        .gold = 0;
+
+    app.command(PlayerCommand::Build {
+        kind: TowerKind::Cannon,
+        cell: (cell.x, cell.y),
+    })
+    .advance_frames(1);
+
+    assert_eq!(app.count::<Tower>(), 0);
+    assert!(!app.world.get::<Block>(block).unwrap().has_tower());
+}
+
+#[test]
+fn selling_a_tower_frees_its_block() {
+    let mut app = headless_game();
+    let (block, cell) = app.buildable_block();
+    let gold_before = gold(&app);
+    let cell = (cell.x, cell.y);
+
+    app.command(PlayerCommand::Build {
+        kind: TowerKind::Cannon,
+        cell,
+    })
+    .advance_frames(1);
+    assert_eq!(app.count::<Tower>(), 1);
+    let gold_after_build = gold(&app);
+    app.command(PlayerCommand::Sell { cell }).advance_frames(1);
+
+    assert_eq!(app.count::<Tower>(), 0);
+    assert_eq!(app.count::<TowerCannon>(), 0);
+    assert!(!app.world.get::<Block>(block).unwrap().has_tower());
+    let cost = gold_before - gold_after_build;
+    let refund = (cost as f32 * SELL_REFUND).round() as u32;
+    assert_eq!(gold(&app), gold_after_build + refund);
+}
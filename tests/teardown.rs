@@ -0,0 +1,123 @@
+use bevy::{app::Events, prelude::*};
+use yatd_lib::{
+    enemy::Enemy,
+    game_state::{GameState, StateScoped},
+    map::{Block, Chunk, ChunkMap, EnemyPath, ExpandMap, PlacementPreview},
+    projectile::Projectile,
+    sim::PlayerCommand,
+    test_support::{headless_game, HeadlessApp, TestApp},
+    tower::{Tower, TowerCannon, TowerKind},
+};
+
+/// Steps the first enemies are waited for, a minute of game time
+const MAX_STEPS: u64 = 60 * 60;
+
+#[test]
+fn leaving_defense_despawns_the_game() {
+    let mut app = headless_game();
+    let (block, cell) = app.buildable_block();
+    app.click_block(block).advance_frames(1);
+    app.command(PlayerCommand::Build {
+        kind: TowerKind::Cannon,
+        cell: (cell.x, cell.y),
+    })
+    .advance_frames(1);
+    assert_eq!(app.count::<Tower>(), 1);
+    let mut steps = 0;
+    while app.count::<Enemy>() == 0 {
+        assert!(steps < MAX_STEPS, "No enemy was spawned");
+        app.step_simulation(1);
+        steps += 1;
+    }
+
+    app.set_state(GameState::End);
+
+    assert_eq!(app.count::<Chunk>(), 0);
+    assert_eq!(app.count::<Block>(), 0);
+    assert_eq!(app.count::<Tower>(), 0);
+    assert_eq!(app.count::<TowerCannon>(), 0);
+    assert_eq!(app.count::<Enemy>(), 0);
+    assert_eq!(app.count::<Projectile>(), 0);
+    assert!(!app.world.contains_resource::<EnemyPath>());
+    assert!(app
+        .world
+        .get_resource::<ChunkMap>()
+        .unwrap()
+        .chunks
+        .is_empty());
+    let preview = app.world.get_resource::<PlacementPreview>().unwrap();
+    assert_eq!(preview.pending, None);
+}
+
+#[test]
+fn only_the_exited_state_is_torn_down() {
+    let mut app = headless_game();
+    let game = app
+        .world
+        .spawn()
+        .insert(StateScoped(GameState::Defense))
+        .id();
+    let child = app.world.spawn().id();
+    app.world.entity_mut(game).push_children(&[child]);
+    let paused = app
+        .world
+        .spawn()
+        .insert(StateScoped(GameState::Paused))
+        .id();
+    let menu = app
+        .world
+        .spawn()
+        .insert(StateScoped(GameState::StartMenu))
+        .id();
+
+    app.push_state(GameState::Paused);
+    assert!(app.world.get_entity(game).is_some());
+
+    app.pop_state();
+    assert!(app.world.get_entity(paused).is_none());
+    assert!(app.world.get_entity(game).is_some());
+
+    app.set_state(GameState::End);
+    assert!(app.world.get_entity(game).is_none());
+    assert!(app.world.get_entity(child).is_none());
+    assert!(app.world.get_entity(menu).is_some());
+}
+
+#[test]
+fn quitting_from_the_pause_menu_despawns_the_game() {
+    let mut app = headless_game();
+    app.push_state(GameState::Paused);
+    let mut state = app.world.get_resource_mut::<State<GameState>>().unwrap();
+    state.overwrite_replace(GameState::StartMenu).unwrap();
+    app.advance_frames(1);
+
+    assert_eq!(app.state(), GameState::StartMenu);
+    assert_eq!(app.count::<Chunk>(), 0);
+    assert_eq!(app.count::<Block>(), 0);
+}
+
+#[test]
+fn leaving_defense_despawns_every_chunk() {
+    let mut app = headless_game();
+    let blocks = app.count::<Block>();
+    app.world
+        .get_resource_mut::<Events<ExpandMap>>()
+        .unwrap()
+        .send(ExpandMap {
+            coord: IVec2::new(1, 0),
+        });
+    app.advance_frames(1);
+    assert_eq!(app.count::<Chunk>(), 2);
+    assert!(app.count::<Block>() > blocks);
+
+    app.set_state(GameState::End);
+
+    assert_eq!(app.count::<Chunk>(), 0);
+    assert_eq!(app.count::<Block>(), 0);
+    assert!(app
+        .world
+        .get_resource::<ChunkMap>()
+        .unwrap()
+        .chunks
+        .is_empty());
+}